@@ -1 +1,8 @@
-from .outlines_core import Guide, Index, Vocabulary
+from .outlines_core import (
+    Guide,
+    Index,
+    MultiGuide,
+    Vocabulary,
+    build_indexes,
+    write_masks_into,
+)
@@ -1,9 +1,13 @@
 from .outlines_core import (  # noqa: F401
+    BINARY,
     BOOLEAN,
+    BYTE,
+    COLOR,
     DATE,
     DATE_TIME,
     EMAIL,
     INTEGER,
+    JsonSchemaError,
     NULL,
     NUMBER,
     STRING,
@@ -13,4 +17,5 @@ from .outlines_core import (  # noqa: F401
     UUID,
     WHITESPACE,
     build_regex_from_schema,
+    build_regex_from_schema_verbose,
 )
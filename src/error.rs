@@ -2,18 +2,56 @@
 
 use thiserror::Error;
 
+use crate::primitives::{StateId, TokenId};
+
 pub type Result<T, E = crate::Error> = std::result::Result<T, E>;
 
+/// Broad category of an [`Error`], stable across new error variants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    Index,
+    Guide,
+    Serialization,
+    Vocabulary,
+    JsonSchema,
+}
+
+/// Errors are `#[non_exhaustive]`, so new variants could be added without breaking callers,
+/// match on [`Error::kind`] for a stable category.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum Error {
     // Index Errors
     #[error("Failed to build DFA {0}")]
     IndexDfaError(#[from] Box<regex_automata::dfa::dense::BuildError>),
     #[error("Index failed since anchored universal start state doesn't exist")]
     DfaHasNoStartState,
+    #[error("Index was built from a different regular expression")]
+    IndexRegexMismatch,
+    #[error("Index has no final states, so generation could never complete")]
+    EmptyLanguage,
+    #[error("Token id {id} is out of range of token masks for vocabulary size {vocab_size}")]
+    TokenIdOutOfRange { id: TokenId, vocab_size: usize },
+    #[error("State {state} doesn't belong to the index")]
+    UnknownState { state: StateId },
+    // Guide Errors
+    #[error("MultiGuide requires at least one index")]
+    MultiGuideWithoutIndexes,
+    #[error("Token {token_id} is not allowed in the current state")]
+    TokenNotAllowed { token_id: TokenId },
+    // Serialization Errors
+    #[error("Serialization failed: {0}")]
+    EncodeError(#[from] bincode::error::EncodeError),
+    #[error("Deserialization failed: {0}")]
+    DecodeError(#[from] bincode::error::DecodeError),
+    #[error("Incompatible serialized version {found}, expected {expected}")]
+    IncompatibleSerializedVersion { found: u16, expected: u16 },
     // Vocabulary Errors
     #[error("EOS token should not be inserted into Vocabulary")]
     EOSTokenDisallowed,
+    #[error("EOS token should not be inserted into Vocabulary, found at entry {index}")]
+    EOSTokenDisallowedAt { index: usize },
     #[error(transparent)]
     TokenizersError(#[from] tokenizers::Error),
     #[error("Unsupported tokenizer for {model}: {reason}, please open an issue with the full error message: https://github.com/dottxt-ai/outlines-core/issues")]
@@ -28,11 +66,15 @@ pub enum Error {
     ByteProcessorFailed,
     #[error("Token processing failed for byte fallback level processor")]
     ByteFallbackProcessorFailed,
+    #[error("Byte fallback token {0} must have exactly two hex digits")]
+    MalformedByteFallbackToken(Box<str>),
     // Json Schema errors
     #[error("serde json error")]
     SerdeJsonError(#[from] serde_json::Error),
     #[error("Unsupported JSON Schema structure {0} \nMake sure it is valid to the JSON Schema specification and check if it's supported by Outlines.\nIf it should be supported, please open an issue.")]
     UnsupportedJsonSchema(Box<serde_json::Value>),
+    #[error("Unsupported JSON Schema keywords: {}", .0.join(", "))]
+    UnsupportedKeywords(Box<[Box<str>]>),
     #[error("'properties' not found or not an object")]
     PropertiesNotFound,
     #[error("'allOf' must be an array")]
@@ -47,6 +89,11 @@ pub enum Error {
     UnsupportedEnumDataType(Box<serde_json::Value>),
     #[error("'enum' must be an array")]
     EnumMustBeAnArray,
+    #[error("Enum value {value} doesn't match type: {instance_type}")]
+    EnumTypeMismatch {
+        value: Box<serde_json::Value>,
+        instance_type: Box<str>,
+    },
     #[error("Unsupported data type in const: {0}")]
     UnsupportedConstDataType(Box<serde_json::Value>),
     #[error("'const' key not found in object")]
@@ -57,21 +104,122 @@ pub enum Error {
     ExternalReferencesNotSupported(Box<str>),
     #[error("Invalid reference format: {0}")]
     InvalidReferenceFormat(Box<str>),
+    #[error("Anchor not found: {0}")]
+    AnchorNotFound(Box<str>),
     #[error("'type' must be a string or an array of string")]
     TypeMustBeAStringOrArray,
     #[error("Unsupported type: {0}")]
     UnsupportedType(Box<str>),
+    #[error("Unsupported type: {instance_type}, use \"{suggestion}\" instead")]
+    UnsupportedTypeAlias {
+        instance_type: Box<str>,
+        suggestion: &'static str,
+    },
     #[error("maxLength must be greater than or equal to minLength")]
     MaxBoundError,
+    #[error("{key} must be a non-negative integer, got {value}")]
+    InvalidBound {
+        key: &'static str,
+        value: Box<serde_json::Value>,
+    },
+    #[error("Invalid pattern {pattern}: {source}")]
+    InvalidPattern {
+        pattern: Box<str>,
+        #[source]
+        source: Box<regex::Error>,
+    },
+    #[error("Too many optional properties {0} to enforce minProperties or maxProperties, the limit is {1}")]
+    TooManyOptionalPropertiesForCount(usize, usize),
+    #[error("Too many properties {0} to allow any order, the limit is {1}")]
+    TooManyPropertiesForAnyOrder(usize, usize),
+    #[error("No combination of properties satisfies minProperties and maxProperties")]
+    UnsatisfiablePropertyCount,
+    #[error("minItems and maxItems can't be satisfied along with {0} prefixItems")]
+    UnsatisfiableItemCount(usize),
+    #[error("maxLength can't be satisfied by a pattern repeated at least {0} times")]
+    UnsatisfiableLength(u64),
+    #[error("minLength and maxLength can't be combined with pattern {0}, only with a single repeated atom like [a-z]+")]
+    UnsupportedPatternWithLength(Box<str>),
     #[error("Format {0} is not supported by Outlines")]
     StringTypeUnsupportedFormat(Box<str>),
+    #[error("'format' only applies to strings, but found on type {0}")]
+    FormatOnNonString(Box<str>),
     #[error("Invalid reference path: {0}")]
     InvalidRefecencePath(Box<str>),
+    #[error("Generated regex can't be parsed: {0}")]
+    RegexSyntaxError(#[from] Box<regex_syntax::Error>),
     #[error("Ref recusion limit reached: {0}")]
     RefRecursionLimitReached(usize),
+    #[error("{source} at JSON pointer '{pointer}'")]
+    JsonSchemaAt {
+        pointer: Box<str>,
+        #[source]
+        source: Box<Error>,
+    },
 }
 
 impl Error {
+    /// Returns the category of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::IndexDfaError(_)
+            | Self::DfaHasNoStartState
+            | Self::IndexRegexMismatch
+            | Self::EmptyLanguage
+            | Self::TokenIdOutOfRange { .. }
+            | Self::UnknownState { .. } => ErrorKind::Index,
+            Self::MultiGuideWithoutIndexes | Self::TokenNotAllowed { .. } => ErrorKind::Guide,
+            Self::EncodeError(_)
+            | Self::DecodeError(_)
+            | Self::IncompatibleSerializedVersion { .. } => ErrorKind::Serialization,
+            Self::EOSTokenDisallowed
+            | Self::EOSTokenDisallowedAt { .. }
+            | Self::TokenizersError(_)
+            | Self::UnsupportedTokenizer { .. }
+            | Self::UnableToLocateEosTokenId { .. }
+            | Self::UnsupportedByTokenProcessor
+            | Self::DecoderUnpackingFailed
+            | Self::ByteProcessorFailed
+            | Self::ByteFallbackProcessorFailed
+            | Self::MalformedByteFallbackToken(_) => ErrorKind::Vocabulary,
+            Self::SerdeJsonError(_)
+            | Self::UnsupportedJsonSchema(_)
+            | Self::UnsupportedKeywords(_)
+            | Self::PropertiesNotFound
+            | Self::AllOfMustBeAnArray
+            | Self::AnyOfMustBeAnArray
+            | Self::OneOfMustBeAnArray
+            | Self::PrefixItemsMustBeAnArray
+            | Self::UnsupportedEnumDataType(_)
+            | Self::EnumMustBeAnArray
+            | Self::EnumTypeMismatch { .. }
+            | Self::UnsupportedConstDataType(_)
+            | Self::ConstKeyNotFound
+            | Self::RefMustBeAString
+            | Self::ExternalReferencesNotSupported(_)
+            | Self::InvalidReferenceFormat(_)
+            | Self::AnchorNotFound(_)
+            | Self::TypeMustBeAStringOrArray
+            | Self::UnsupportedType(_)
+            | Self::UnsupportedTypeAlias { .. }
+            | Self::MaxBoundError
+            | Self::InvalidBound { .. }
+            | Self::InvalidPattern { .. }
+            | Self::TooManyOptionalPropertiesForCount(..)
+            | Self::TooManyPropertiesForAnyOrder(..)
+            | Self::UnsatisfiablePropertyCount
+            | Self::UnsatisfiableItemCount(_)
+            | Self::UnsatisfiableLength(_)
+            | Self::UnsupportedPatternWithLength(_)
+            | Self::StringTypeUnsupportedFormat(_)
+            | Self::FormatOnNonString(_)
+            | Self::InvalidRefecencePath(_)
+            | Self::RegexSyntaxError(_)
+            | Self::RefRecursionLimitReached(_)
+            | Self::JsonSchemaAt { .. } => ErrorKind::JsonSchema,
+        }
+    }
+
     pub fn is_recursion_limit(&self) -> bool {
         matches!(self, Self::RefRecursionLimitReached(_))
     }
@@ -85,3 +233,31 @@ impl From<Error> for pyo3::PyErr {
         PyErr::new::<PyValueError, _>(e.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_kinds() {
+        assert_eq!(Error::DfaHasNoStartState.kind(), ErrorKind::Index);
+        assert_eq!(Error::UnknownState { state: 1 }.kind(), ErrorKind::Index);
+        assert_eq!(Error::MultiGuideWithoutIndexes.kind(), ErrorKind::Guide);
+        assert_eq!(
+            Error::IncompatibleSerializedVersion {
+                found: 1,
+                expected: 2
+            }
+            .kind(),
+            ErrorKind::Serialization
+        );
+        assert_eq!(Error::EOSTokenDisallowed.kind(), ErrorKind::Vocabulary);
+        assert_eq!(Error::RefMustBeAString.kind(), ErrorKind::JsonSchema);
+
+        let located = Error::JsonSchemaAt {
+            pointer: Box::from("/properties/a"),
+            source: Box::new(Error::RefRecursionLimitReached(3)),
+        };
+        assert_eq!(located.kind(), ErrorKind::JsonSchema);
+    }
+}
@@ -0,0 +1,164 @@
+//! Guidance of generation by several indexes at once.
+
+use std::sync::Arc;
+
+use rustc_hash::FxHashSet as HashSet;
+
+use crate::index::Index;
+use crate::prelude::*;
+use crate::{Error, Result};
+
+/// `MultiGuide` guides generation to satisfy several indexes simultaneously.
+///
+/// Every index is advanced by each token, so only tokens allowed by all of them are allowed,
+/// and generation is finished only when all of them are in a final state. Indexes are expected
+/// to be built with the same vocabulary.
+///
+/// ## Example
+/// ```rust
+/// # use outlines_core::Error;
+/// use std::sync::Arc;
+/// use outlines_core::guide::MultiGuide;
+/// use outlines_core::prelude::*;
+///
+/// # fn main() -> Result<(), Error> {
+/// let vocabulary = Vocabulary::from_iter_str(3, [("1", 0), ("2", 1), ("a", 2)])?;
+/// let digits = Arc::new(Index::new("[0-9]+", &vocabulary)?);
+/// let short = Arc::new(Index::new(".{1,2}", &vocabulary)?);
+///
+/// let mut guide = MultiGuide::new(vec![digits, short])?;
+/// assert_eq!(guide.allowed_tokens(), vec![0, 1]);
+///
+/// guide.advance(0)?;
+/// guide.advance(1)?;
+/// assert!(guide.is_finished());
+/// assert_eq!(guide.allowed_tokens(), vec![3]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultiGuide {
+    indexes: Vec<Arc<Index>>,
+    states: Vec<StateId>,
+}
+
+impl MultiGuide {
+    /// Creates a `MultiGuide` at the initial state of every index.
+    pub fn new(indexes: Vec<Arc<Index>>) -> Result<Self> {
+        if indexes.is_empty() {
+            return Err(Error::MultiGuideWithoutIndexes);
+        }
+        let states = indexes.iter().map(|index| index.initial_state()).collect();
+        Ok(Self { indexes, states })
+    }
+
+    /// Returns the current state of every index, in the order of indexes.
+    pub fn states(&self) -> &[StateId] {
+        &self.states
+    }
+
+    /// Returns tokens allowed by all indexes in their current states, in ascending order.
+    pub fn allowed_tokens(&self) -> Vec<TokenId> {
+        let mut allowed: Option<HashSet<TokenId>> = None;
+        for (index, state) in self.indexes.iter().zip(&self.states) {
+            let tokens = index
+                .allowed_tokens_iter(state)
+                .into_iter()
+                .flatten()
+                .copied();
+            allowed = Some(match allowed {
+                None => tokens.collect(),
+                Some(allowed) => tokens
+                    .filter(|token_id| allowed.contains(token_id))
+                    .collect(),
+            });
+        }
+        let mut allowed: Vec<TokenId> = allowed.unwrap_or_default().into_iter().collect();
+        allowed.sort_unstable();
+        allowed
+    }
+
+    /// Advances every index by the token and returns tokens allowed next.
+    ///
+    /// If any index doesn't allow the token, no index is advanced.
+    pub fn advance(&mut self, token_id: TokenId) -> Result<Vec<TokenId>> {
+        let next_states = self
+            .indexes
+            .iter()
+            .zip(&self.states)
+            .map(|(index, state)| index.next_state(state, &token_id))
+            .collect::<Option<Vec<StateId>>>()
+            .ok_or(Error::TokenNotAllowed { token_id })?;
+        self.states = next_states;
+        Ok(self.allowed_tokens())
+    }
+
+    /// Checks if every index is in a final state.
+    pub fn is_finished(&self) -> bool {
+        self.indexes
+            .iter()
+            .zip(&self.states)
+            .all(|(index, state)| index.is_final_state(state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guide(regexes: &[&str]) -> MultiGuide {
+        let vocabulary =
+            Vocabulary::from_iter_str(5, [("a", 0), ("b", 1), ("ab", 2), ("1", 3), ("ba", 4)])
+                .expect("Vocabulary failed");
+        let indexes = regexes
+            .iter()
+            .map(|regex| Arc::new(Index::new(regex, &vocabulary).expect("Index failed")))
+            .collect();
+        MultiGuide::new(indexes).expect("MultiGuide failed")
+    }
+
+    #[test]
+    fn intersection_of_allowed_tokens() {
+        // Letters only and at most three characters.
+        let mut guide = guide(&["[ab]+", ".{1,3}"]);
+        assert_eq!(guide.allowed_tokens(), vec![0, 1, 2, 4]);
+        assert!(!guide.is_finished());
+
+        assert_eq!(guide.advance(2).expect("Advance failed"), vec![0, 1, 5]);
+        assert!(guide.is_finished());
+
+        assert_eq!(guide.advance(1).expect("Advance failed"), vec![5]);
+        assert!(guide.is_finished());
+    }
+
+    #[test]
+    fn disallowed_token_keeps_states() {
+        let mut guide = guide(&["[ab]+", "a.*"]);
+        assert_eq!(guide.allowed_tokens(), vec![0, 2]);
+
+        let states = guide.states().to_vec();
+        assert!(matches!(
+            guide.advance(1),
+            Err(Error::TokenNotAllowed { token_id: 1 })
+        ));
+        assert_eq!(guide.states(), states);
+    }
+
+    #[test]
+    fn finished_only_when_all_final() {
+        let mut guide = guide(&["a+", "aa"]);
+        assert_eq!(guide.advance(0).expect("Advance failed"), vec![0]);
+        assert!(!guide.is_finished());
+
+        assert_eq!(guide.advance(0).expect("Advance failed"), vec![5]);
+        assert!(guide.is_finished());
+    }
+
+    #[test]
+    fn indexes_are_required() {
+        assert!(matches!(
+            MultiGuide::new(vec![]),
+            Err(Error::MultiGuideWithoutIndexes)
+        ));
+    }
+}
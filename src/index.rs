@@ -1,18 +1,91 @@
 //! Building an `Index` to efficiently map vocabulary tokens to state transitions.
 
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{Read, Write};
+
+use bincode::de::Decoder;
+use bincode::enc::Encoder;
+use bincode::error::{DecodeError, EncodeError};
 use bincode::{Decode, Encode};
 use regex_automata::dfa::dense::DFA;
 use regex_automata::dfa::Automaton;
 use regex_automata::util::primitives::StateID as AutomataStateId;
+use regex_automata::util::syntax;
 use regex_automata::Anchored;
 use rustc_hash::{FxHashMap as HashMap, FxHashSet as HashSet};
 
+use crate::fingerprint::fingerprint;
+use crate::json_schema::{self, GenerationOptions};
 use crate::prelude::*;
+use crate::serialization::{self, DecodeContext};
 use crate::vocabulary::Vocabulary;
 use crate::{Error, Result};
 
+/// Issues found in a regular expression, which `Index` construction worked around.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Warning {
+    /// Leading `^` was stripped, since the index always matches from the start.
+    StartAnchorStripped,
+    /// Trailing `$` was stripped, since the index always matches until the end.
+    EndAnchorStripped,
+}
+
+/// Flags of a regular expression, applied to the whole expression when building `Index`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RegexFlags {
+    /// Letters match both their upper and lower case forms, same as the `i` flag.
+    pub case_insensitive: bool,
+    /// `.` matches `\n` as well, same as the `s` flag.
+    pub dot_matches_new_line: bool,
+}
+
+/// Configuration of `Index` construction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndexConfig {
+    /// Final states get a self-loop transition of EOS token, otherwise they're only known
+    /// by [`Index::is_final_state`]. Defaults to `true`.
+    pub add_eos_self_loops: bool,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            add_eos_self_loops: true,
+        }
+    }
+}
+
+/// Structural differences between two indexes, as returned by `Index::diff`.
+///
+/// Entries of every list are sorted in ascending order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IndexDiff {
+    /// States present only in the other index.
+    pub added_states: Vec<StateId>,
+    /// States present only in this index.
+    pub removed_states: Vec<StateId>,
+    /// Transitions `(state, token_id, next_state)` present only in the other index.
+    pub added_transitions: Vec<(StateId, TokenId, StateId)>,
+    /// Transitions `(state, token_id, next_state)` present only in this index.
+    pub removed_transitions: Vec<(StateId, TokenId, StateId)>,
+    /// Transitions `(state, token_id, next_state, other_next_state)` present in both indexes,
+    /// but leading to different states.
+    pub changed_transitions: Vec<(StateId, TokenId, StateId, StateId)>,
+    /// Final states present only in the other index.
+    pub added_final_states: Vec<StateId>,
+    /// Final states present only in this index.
+    pub removed_final_states: Vec<StateId>,
+}
+
+impl IndexDiff {
+    /// Checks if no differences were found.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 /// `Index` efficiently maps vocabulary tokens to state transitions.
-#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Index {
     /// The ID of the initial state in the automaton, processing begins from this state.
     initial_state: StateId,
@@ -57,7 +130,45 @@ pub struct Index {
     eos_token_id: TokenId,
     /// The size of the vocabulary used to build the index.
     vocab_size: usize,
+    /// Fingerprint of the DFA the states come from, `None` if states were numbered anew.
+    dfa_fingerprint: Option<u64>,
+    /// All states of the index, derived from other fields for quick lookups, so it's not
+    /// serialized.
+    states: HashSet<StateId>,
+}
+
+impl Encode for Index {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        self.initial_state.encode(encoder)?;
+        self.final_states.encode(encoder)?;
+        self.transitions.encode(encoder)?;
+        self.eos_token_id.encode(encoder)?;
+        self.vocab_size.encode(encoder)?;
+        self.dfa_fingerprint.encode(encoder)
+    }
+}
+
+impl Decode<DecodeContext> for Index {
+    fn decode<D: Decoder<Context = DecodeContext>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let index = Index {
+            initial_state: Decode::decode(decoder)?,
+            final_states: Decode::decode(decoder)?,
+            transitions: Decode::decode(decoder)?,
+            eos_token_id: Decode::decode(decoder)?,
+            vocab_size: Decode::decode(decoder)?,
+            // Layout of version 1 ends right after the vocabulary size.
+            dfa_fingerprint: if decoder.context().version >= 2 {
+                Decode::decode(decoder)?
+            } else {
+                None
+            },
+            states: HashSet::default(),
+        };
+        Ok(index.with_collected_states())
+    }
 }
+
+bincode::impl_borrow_decode_with_context!(Index, DecodeContext);
 /// The `Index` structure is designed to efficiently map tokens from a given vocabulary
 /// to state transitions within a finite-state automaton.
 ///
@@ -100,71 +211,330 @@ pub struct Index {
 ///   which may require a considerable amount of time and computational resources.
 impl Index {
     /// Builds an `Index` from regular expression and vocabulary tokens.
+    ///
+    /// Fails with [`Error::EmptyLanguage`] if no sequence of tokens matches the regular expression.
     pub fn new(regex: &str, vocabulary: &Vocabulary) -> Result<Self> {
-        let vocab_size = vocabulary.len();
-        let eos_token_id = vocabulary.eos_token_id();
         let dfa = DFA::new(regex).map_err(Box::new)?;
+        Self::from_dfa(&dfa, vocabulary)
+    }
+
+    /// Builds an `Index` like [`Index::new`], but with the regular expression's flags.
+    pub fn new_with_flags(regex: &str, vocabulary: &Vocabulary, flags: RegexFlags) -> Result<Self> {
+        let dfa = DFA::builder()
+            .syntax(
+                syntax::Config::new()
+                    .case_insensitive(flags.case_insensitive)
+                    .dot_matches_new_line(flags.dot_matches_new_line),
+            )
+            .build(regex)
+            .map_err(Box::new)?;
+        Self::from_dfa(&dfa, vocabulary)
+    }
+
+    /// Builds an `Index` from a JSON schema with given [`GenerationOptions`], in one call.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use outlines_core::Error;
+    /// use outlines_core::prelude::*;
+    /// use outlines_core::json_schema::GenerationOptions;
+    ///
+    /// # fn main() -> Result<(), Error> {
+    ///     let schema = r#"{"type": "integer"}"#;
+    ///
+    ///     let mut vocabulary = Vocabulary::new(3);
+    ///     for (token, token_id) in [("1", 0), ("2", 1), ("-", 2)] {
+    ///         vocabulary.try_insert(token, token_id)?;
+    ///     }
+    ///
+    ///     let index = Index::new_from_schema(schema, &vocabulary, &GenerationOptions::default())?;
+    ///     let allowed_tokens = index.allowed_tokens(&index.initial_state()).expect("Allowed tokens");
+    ///     println!("Allowed tokens at initial state are {:?}", allowed_tokens);
+    /// #   Ok(())
+    /// }
+    /// ```
+    pub fn new_from_schema(
+        schema: &str,
+        vocabulary: &Vocabulary,
+        options: &GenerationOptions,
+    ) -> Result<Self> {
+        Self::new_from_schema_with_regex(schema, vocabulary, options).map(|(index, _)| index)
+    }
+
+    /// Builds an `Index` like [`Index::new_from_schema`], but also returns the regular
+    /// expression generated from the schema.
+    pub fn new_from_schema_with_regex(
+        schema: &str,
+        vocabulary: &Vocabulary,
+        options: &GenerationOptions,
+    ) -> Result<(Self, String)> {
+        let json_value: serde_json::Value = serde_json::from_str(schema)?;
+        let regex = json_schema::regex_from_value_with_options(&json_value, options)?;
+        let index = Self::new(&regex, vocabulary)?;
+        Ok((index, regex))
+    }
+
+    /// Builds an `Index` like [`Index::new`], but with the construction configuration.
+    pub fn new_with_config(
+        regex: &str,
+        vocabulary: &Vocabulary,
+        config: IndexConfig,
+    ) -> Result<Self> {
+        let mut index = Self::new(regex, vocabulary)?;
+        if !config.add_eos_self_loops {
+            index.remove_eos_self_loops();
+        }
+        Ok(index)
+    }
+
+    /// Builds an `Index` like [`Index::new`], but strips redundant anchors of the regular
+    /// expression first, reporting each of them as a [`Warning`].
+    pub fn new_with_warnings(regex: &str, vocabulary: &Vocabulary) -> Result<(Self, Vec<Warning>)> {
+        let mut warnings = Vec::new();
+        let mut stripped = regex;
+        if let Some(rest) = stripped.strip_prefix('^') {
+            stripped = rest;
+            warnings.push(Warning::StartAnchorStripped);
+        }
+        if let Some(rest) = stripped.strip_suffix('$') {
+            if !rest.ends_with('\\') {
+                stripped = rest;
+                warnings.push(Warning::EndAnchorStripped);
+            }
+        }
+        if warnings.is_empty() {
+            return Ok((Self::new(regex, vocabulary)?, warnings));
+        }
+        // The group keeps alternations, like `^a|b$`, applying to the whole expression.
+        let index = Self::new(&format!("(?:{})", stripped), vocabulary)?;
+        Ok((index, warnings))
+    }
+
+    /// Builds an `Index` accepting the union of several regular expressions.
+    ///
+    /// Regular expressions are compiled together as separate patterns of one DFA,
+    /// so there is no need to join, group or anchor them manually.
+    pub fn union(regexes: &[&str], vocabulary: &Vocabulary) -> Result<Self> {
+        let dfa = DFA::new_many(regexes).map_err(Box::new)?;
+        Self::from_dfa(&dfa, vocabulary)
+    }
+
+    /// Builds an `Index` for each of the vocabularies from a single regular expression.
+    ///
+    /// The regular expression is compiled only once and indexes are built concurrently,
+    /// which is useful when serving multiple models with slightly different vocabularies.
+    pub fn new_many(regex: &str, vocabularies: &[&Vocabulary]) -> Result<Vec<Self>> {
+        let dfa = DFA::new(regex).map_err(Box::new)?;
+        let threads = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        let chunk_size = vocabularies.len().div_ceil(threads).max(1);
+
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = vocabularies
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    let dfa = &dfa;
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|vocabulary| Self::from_dfa(dfa, vocabulary))
+                            .collect::<Result<Vec<_>>>()
+                    })
+                })
+                .collect();
+
+            let mut indexes = Vec::with_capacity(vocabularies.len());
+            for handle in handles {
+                match handle.join() {
+                    Ok(chunk) => indexes.extend(chunk?),
+                    Err(panic) => std::panic::resume_unwind(panic),
+                }
+            }
+            Ok(indexes)
+        })
+    }
+
+    /// Builds an `Index` from an already built DFA and vocabulary tokens, so the DFA
+    /// configuration is fully under the caller's control.
+    ///
+    /// The DFA must support anchored searches and have a universal anchored start state,
+    /// otherwise [`Error::DfaHasNoStartState`] is returned.
+    pub fn from_dfa(dfa: &DFA<Vec<u32>>, vocabulary: &Vocabulary) -> Result<Self> {
         let start_state = match dfa.universal_start_state(Anchored::Yes) {
             Some(s) => s,
             None => return Err(Error::DfaHasNoStartState),
         };
 
-        let mut transitions: HashMap<StateId, HashMap<TokenId, StateId>> = HashMap::default();
-        let mut final_states: HashSet<StateId> = HashSet::default();
-
+        let mut index = Self {
+            initial_state: start_state.as_u32(),
+            final_states: HashSet::default(),
+            transitions: HashMap::default(),
+            eos_token_id: vocabulary.eos_token_id(),
+            vocab_size: vocabulary.len(),
+            dfa_fingerprint: Some(dfa_fingerprint(dfa)),
+            states: HashSet::default(),
+        };
         let mut seen: HashSet<AutomataStateId> = HashSet::from_iter([start_state]);
-        let mut next_states: Vec<AutomataStateId> = vec![start_state];
+        index.explore(dfa, vocabulary, &mut seen, vec![start_state]);
 
-        while let Some(current_state) = next_states.pop() {
-            if dfa.is_match_state(dfa.next_eoi_state(current_state)) {
-                final_states.insert(current_state.as_u32());
+        // Either the regex matches nothing or no tokens' sequence completes a match.
+        if index.final_states.is_empty() {
+            return Err(Error::EmptyLanguage);
+        }
+        Ok(index.with_collected_states())
+    }
+
+    /// Extends the index with new tokens, without rebuilding it from scratch.
+    ///
+    /// New tokens are walked from every existing state and states discovered by them are
+    /// explored with the whole `vocabulary`, which is expected to already contain new tokens.
+    ///
+    /// The `regex` must be the same regular expression the index was built from by
+    /// [`Index::new`], otherwise [`Error::IndexRegexMismatch`] is returned.
+    pub fn add_tokens(
+        &mut self,
+        regex: &str,
+        vocabulary: &Vocabulary,
+        new_tokens: &[(Token, TokenId)],
+    ) -> Result<()> {
+        let dfa = DFA::new(regex).map_err(Box::new)?;
+        self.add_tokens_from_dfa(&dfa, vocabulary, new_tokens)
+    }
+
+    /// Extends the index with new tokens like [`Index::add_tokens`], but with an already built
+    /// DFA, which must be the same the index was built from.
+    pub fn add_tokens_from_dfa(
+        &mut self,
+        dfa: &DFA<Vec<u32>>,
+        vocabulary: &Vocabulary,
+        new_tokens: &[(Token, TokenId)],
+    ) -> Result<()> {
+        if new_tokens.iter().any(|(_, id)| *id == self.eos_token_id) {
+            return Err(Error::EOSTokenDisallowed);
+        }
+        if self.dfa_fingerprint != Some(dfa_fingerprint(dfa)) {
+            return Err(Error::IndexRegexMismatch);
+        }
+
+        let states = self
+            .states
+            .iter()
+            .map(|state| AutomataStateId::new(*state as usize))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| Error::IndexRegexMismatch)?;
+
+        let mut seen: HashSet<AutomataStateId> = HashSet::from_iter(states.iter().copied());
+        let mut next_states: Vec<AutomataStateId> = Vec::new();
+        for current_state in states {
+            for (token, token_id) in new_tokens {
+                self.add_token_transition(
+                    dfa,
+                    current_state,
+                    token,
+                    &[*token_id],
+                    &mut seen,
+                    &mut next_states,
+                );
             }
+        }
+        self.explore(dfa, vocabulary, &mut seen, next_states);
+        self.vocab_size = vocabulary.len();
+        self.states = self.collect_states();
 
-            'token_loop: for (token, ids) in vocabulary.tokens().iter() {
-                if ids.contains(&eos_token_id) {
-                    continue;
-                }
+        Ok(())
+    }
 
-                let mut next_state = current_state;
-                for transition_byte in token {
-                    next_state = dfa.next_state(next_state, *transition_byte);
-                    if dfa.is_dead_state(next_state) || dfa.is_quit_state(next_state) {
-                        continue 'token_loop;
-                    }
+    /// Removes self-loop transitions of EOS token from final states.
+    ///
+    /// Final states stay in the transitions, even if left without allowed tokens. Note, that
+    /// [`Index::add_tokens`] adds self-loops to newly discovered final states.
+    pub fn remove_eos_self_loops(&mut self) {
+        for state in self.final_states.iter() {
+            if let Some(map) = self.transitions.get_mut(state) {
+                if map.get(&self.eos_token_id) == Some(state) {
+                    map.remove(&self.eos_token_id);
                 }
+            }
+        }
+    }
 
-                let is_intermediate_state = !dfa.is_match_state(next_state);
-                let is_full_match_state = dfa.is_match_state(dfa.next_eoi_state(next_state));
-                if is_intermediate_state || is_full_match_state {
-                    for token_id in ids {
-                        transitions
-                            .entry(current_state.as_u32())
-                            .or_default()
-                            .insert(*token_id, next_state.as_u32());
-                    }
-                }
-                if !seen.contains(&next_state) {
-                    seen.insert(next_state);
-                    next_states.push(next_state);
+    /// Returns the index with `states` collected from its other fields.
+    fn with_collected_states(mut self) -> Self {
+        self.states = self.collect_states();
+        self
+    }
+
+    /// Collects the initial state, final states and states on either side of any transition.
+    fn collect_states(&self) -> HashSet<StateId> {
+        let mut states: HashSet<StateId> = HashSet::from_iter([self.initial_state]);
+        states.extend(self.final_states.iter().copied());
+        for (state, map) in self.transitions.iter() {
+            states.insert(*state);
+            states.extend(map.values().copied());
+        }
+        states
+    }
+
+    /// Explores all `next_states` and states reachable from them with vocabulary tokens.
+    fn explore(
+        &mut self,
+        dfa: &DFA<Vec<u32>>,
+        vocabulary: &Vocabulary,
+        seen: &mut HashSet<AutomataStateId>,
+        mut next_states: Vec<AutomataStateId>,
+    ) {
+        while let Some(current_state) = next_states.pop() {
+            if dfa.is_match_state(dfa.next_eoi_state(current_state)) {
+                // Final states get a mapping to `eos_token_id`.
+                self.final_states.insert(current_state.as_u32());
+                self.transitions
+                    .entry(current_state.as_u32())
+                    .or_default()
+                    .insert(self.eos_token_id, current_state.as_u32());
+            }
+
+            for (token, ids) in vocabulary.tokens().iter() {
+                if ids.contains(&self.eos_token_id) {
+                    continue;
                 }
+                self.add_token_transition(dfa, current_state, token, ids, seen, &mut next_states);
             }
         }
+    }
 
-        // Populate `transitions` with mappings from `final_states` to `eos_token_id`
-        for &final_state in &final_states {
-            transitions
-                .entry(final_state)
-                .or_default()
-                .insert(eos_token_id, final_state);
+    /// Walks the token from the state and records the transition if it's allowed.
+    fn add_token_transition(
+        &mut self,
+        dfa: &DFA<Vec<u32>>,
+        current_state: AutomataStateId,
+        token: &[u8],
+        ids: &[TokenId],
+        seen: &mut HashSet<AutomataStateId>,
+        next_states: &mut Vec<AutomataStateId>,
+    ) {
+        let mut next_state = current_state;
+        for transition_byte in token {
+            next_state = dfa.next_state(next_state, *transition_byte);
+            if dfa.is_dead_state(next_state) || dfa.is_quit_state(next_state) {
+                return;
+            }
         }
 
-        Ok(Self {
-            initial_state: start_state.as_u32(),
-            final_states,
-            transitions,
-            eos_token_id,
-            vocab_size,
-        })
+        let is_intermediate_state = !dfa.is_match_state(next_state);
+        let is_full_match_state = dfa.is_match_state(dfa.next_eoi_state(next_state));
+        if is_intermediate_state || is_full_match_state {
+            for token_id in ids {
+                self.transitions
+                    .entry(current_state.as_u32())
+                    .or_default()
+                    .insert(*token_id, next_state.as_u32());
+            }
+        }
+        if seen.insert(next_state) {
+            next_states.push(next_state);
+        }
     }
 
     /// Returns the ID of the initial state in the automaton.
@@ -172,6 +542,15 @@ impl Index {
         self.initial_state
     }
 
+    /// Checks if the initial state is final, i.e. the regex matches an empty string.
+    ///
+    /// In that case the generation is finished before any token is generated and EOS token
+    /// is allowed right away, so generation loops checking for a final state should be ready
+    /// to produce a valid zero-length output.
+    pub fn initial_is_final(&self) -> bool {
+        self.final_states.contains(&self.initial_state)
+    }
+
     /// Returns set of final states.
     pub fn final_states(&self) -> &HashSet<StateId> {
         &self.final_states
@@ -182,11 +561,32 @@ impl Index {
         &self.transitions
     }
 
+    /// Iterates over all transitions as `(state, token_id, next_state)` triples, without cloning.
+    pub fn transitions_iter(&self) -> impl Iterator<Item = (StateId, TokenId, StateId)> + '_ {
+        self.transitions.iter().flat_map(|(state, map)| {
+            map.iter()
+                .map(move |(token_id, next_state)| (*state, *token_id, *next_state))
+        })
+    }
+
     /// Checks if state is in final states set or not.
     pub fn is_final_state(&self, state: &StateId) -> bool {
         self.final_states.contains(state)
     }
 
+    /// Checks if generation may stop at the state, same as [`Index::is_final_state`].
+    pub fn can_terminate(&self, state: &StateId) -> bool {
+        self.is_final_state(state)
+    }
+
+    /// Checks if generation must stop at the state, i.e. the only allowed token is EOS.
+    pub fn must_terminate(&self, state: &StateId) -> bool {
+        self.transitions
+            .get(state)
+            .is_some_and(|map| map.keys().all(|token_id| *token_id == self.eos_token_id))
+            && self.is_final_state(state)
+    }
+
     /// Lists allowed tokens for a give state ID or `None` if it is not found in `Index`.
     pub fn allowed_tokens(&self, state: &StateId) -> Option<Vec<TokenId>> {
         self.transitions
@@ -206,113 +606,1510 @@ impl Index {
         Some(*self.transitions.get(state)?.get(token_id)?)
     }
 
+    /// Returns the next state for the token, same as [`Index::next_state`], but fails with
+    /// [`Error::UnknownState`] if the state doesn't belong to the index, instead of returning
+    /// `None` as for a disallowed token.
+    pub fn checked_next_state(
+        &self,
+        state: &StateId,
+        token_id: &TokenId,
+    ) -> Result<Option<StateId>> {
+        if !self.contains_state(state) {
+            return Err(Error::UnknownState { state: *state });
+        }
+        Ok(self.next_state(state, token_id))
+    }
+
+    /// Walks tokens from the initial state, yielding the state after each token.
+    ///
+    /// Once a token isn't allowed, `None` is yielded and the walk ends.
+    pub fn walk<'a>(&'a self, tokens: &'a [TokenId]) -> impl Iterator<Item = Option<StateId>> + 'a {
+        let mut state = Some(self.initial_state);
+        tokens.iter().map_while(move |token_id| {
+            let current_state = state?;
+            state = self.next_state(&current_state, token_id);
+            Some(state)
+        })
+    }
+
+    /// Checks if the state belongs to the index: it's the initial state, a final state
+    /// or it's reachable by transitions, e.g. to validate a state obtained elsewhere.
+    pub fn contains_state(&self, state: &StateId) -> bool {
+        self.states.contains(state)
+    }
+
     pub fn vocab_size(&self) -> usize {
         self.vocab_size
     }
-}
 
-impl std::fmt::Display for Index {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "Index object with transitions:")?;
-        for (state_id, token_ids) in self.transitions.iter() {
-            writeln!(f, "{:?} -> {:#?}", state_id, token_ids)?;
+    /// Returns the exclusive upper bound of token ids for the vocabulary size, so that token
+    /// masks of `vocab_size.div_ceil(32)` 32-bit elements could hold every token.
+    pub fn max_token_id_bound(vocab_size: usize) -> usize {
+        vocab_size.div_ceil(32) * 32
+    }
+
+    /// Checks that every token id of the index, including EOS token id, is less than
+    /// [`Index::max_token_id_bound`] of its vocabulary size, failing with
+    /// [`Error::TokenIdOutOfRange`] otherwise.
+    ///
+    /// The check is opt-in and isn't done by [`Index::new`]: sparse vocabularies, with ids
+    /// beyond their size, e.g. when special tokens are skipped, are valid to build an index,
+    /// as long as masks are sized by the model's vocabulary rather than by `vocab_size`.
+    pub fn validate_token_ids(&self) -> Result<()> {
+        let bound = Self::max_token_id_bound(self.vocab_size);
+        let out_of_range = self
+            .transitions_iter()
+            .map(|(_, token_id, _)| token_id)
+            .chain([self.eos_token_id])
+            .filter(|token_id| *token_id as usize >= bound)
+            .max();
+        match out_of_range {
+            Some(id) => Err(Error::TokenIdOutOfRange {
+                id,
+                vocab_size: self.vocab_size,
+            }),
+            None => Ok(()),
         }
-        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Serializes the index straight into a writer, e.g. a file.
+    ///
+    /// The blob is prepended by a versioned header, see [`crate::serialization`].
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+        serialization::encode_into_writer(self, writer)
+    }
 
-    #[test]
-    fn index_from_regex() {
-        let regex = "0|[1-9][0-9]*";
-        let eos_token_id = 4;
-        let mut vocabulary = Vocabulary::new(eos_token_id);
-        for (token, token_id) in [("blah", 0), ("1a", 1), ("2", 2), ("0", 3)] {
-            vocabulary
-                .try_insert(token, token_id as u32)
-                .expect("Insert failed");
-        }
-        let index = Index::new(regex, &vocabulary).expect("Index failed");
-        let initial_state = index.initial_state();
-        assert_eq!(initial_state, 40);
-        assert_eq!(index.final_states(), &HashSet::from_iter([24, 48, 56]));
-        assert!(!index.is_final_state(&initial_state));
+    /// Deserializes an index previously written by [`Index::write_to`] from a reader.
+    pub fn read_from<R: Read>(reader: R) -> Result<Self> {
+        serialization::decode_from_reader(reader)
+    }
 
-        let expected = HashMap::from_iter([
-            (24, HashMap::from_iter([(3, 24), (4, 24), (2, 24)])),
-            (48, HashMap::from_iter([(4, 48)])),
-            (40, HashMap::from_iter([(3, 48), (2, 56)])),
-            (56, HashMap::from_iter([(3, 24), (4, 56), (2, 24)])),
-        ]);
-        assert_eq!(index.transitions(), &expected);
+    /// Returns allowed tokens from the state, which lead into a final state, in ascending order.
+    /// EOS token is never included.
+    pub fn terminating_tokens(&self, state: &StateId) -> Vec<TokenId> {
+        let mut token_ids: Vec<TokenId> = self
+            .transitions
+            .get(state)
+            .into_iter()
+            .flatten()
+            .filter(|(token_id, next_state)| {
+                **token_id != self.eos_token_id && self.is_final_state(next_state)
+            })
+            .map(|(token_id, _)| *token_id)
+            .collect();
+        token_ids.sort_unstable();
+        token_ids
+    }
 
-        let allowed_tokens = index
-            .allowed_tokens(&initial_state)
-            .expect("No allowed tokens");
-        let token_id = allowed_tokens.first().expect("No first tokens");
+    /// Returns ids of all tokens, which appear on any transition, i.e. the only tokens of
+    /// the vocabulary ever allowed by the index. EOS token is never included.
+    pub fn used_token_ids(&self) -> HashSet<TokenId> {
+        self.transitions
+            .values()
+            .flat_map(|map| map.keys().copied())
+            .filter(|token_id| *token_id != self.eos_token_id)
+            .collect()
+    }
 
-        let state = 48;
-        assert_eq!(index.next_state(&initial_state, token_id), Some(state));
-        assert!(index.is_final_state(&state));
+    /// Returns the number of distinct states, i.e. the initial state, final states and states
+    /// on either side of any transition.
+    pub fn num_states(&self) -> usize {
+        self.states.len()
+    }
 
-        assert_eq!(index.next_state(&state, &eos_token_id), None);
-        assert_eq!(index.next_state(&state, token_id), None);
+    /// Returns the number of transitions, including self-loops of EOS token.
+    pub fn num_transitions(&self) -> usize {
+        self.transitions.values().map(|map| map.len()).sum()
     }
 
-    #[test]
-    fn index_from_regex_initital_in_allowed() {
-        let regex = "`\\n(\\.\\n)?`\\n";
-        let mut vocabulary = Vocabulary::new(104);
-        for (token, token_id) in [("\n", 103), (".", 102), ("`", 101)] {
-            vocabulary
-                .try_insert(token, token_id as u32)
-                .expect("Insert failed");
+    /// Returns states reachable from the initial state, including the initial state itself.
+    pub fn reachable_states(&self) -> HashSet<StateId> {
+        let mut reachable = HashSet::from_iter([self.initial_state]);
+        let mut queue = VecDeque::from([self.initial_state]);
+        while let Some(state) = queue.pop_front() {
+            if let Some(map) = self.transitions.get(&state) {
+                for next_state in map.values() {
+                    if reachable.insert(*next_state) {
+                        queue.push_back(*next_state);
+                    }
+                }
+            }
         }
+        reachable
+    }
 
-        let index = Index::new(regex, &vocabulary).expect("Index failed");
-        let allowed = index
-            .allowed_tokens(&index.initial_state())
-            .expect("No allowed tokens");
-        assert!(allowed.contains(&101));
+    /// Returns tokens allowed at the initial state in ascending order, including EOS token
+    /// if the initial state is final. See also [`tokens_matching_prefix`].
+    pub fn startable_tokens(&self) -> Vec<TokenId> {
+        let mut token_ids = self.allowed_tokens(&self.initial_state).unwrap_or_default();
+        token_ids.sort_unstable();
+        token_ids
     }
 
-    #[test]
-    fn index_from_regex_multibyte() {
-        let regex = "😇| [😈-😍][😇-😎]*";
-        let mut vocabulary = Vocabulary::new(8);
-        for (token, token_id) in [(" 😍", 5), ("blah", 0), ("😇", 2), ("😈a", 1), ("😍", 3)]
-        {
-            vocabulary
-                .try_insert(token, token_id as u32)
-                .expect("Insert failed");
+    /// Returns a shortest sequence of token ids leading from the initial state to `target`,
+    /// or `None` if `target` isn't reachable. EOS token is never a part of the sequence.
+    pub fn shortest_token_path(&self, target: &StateId) -> Option<Vec<TokenId>> {
+        let mut predecessors: HashMap<StateId, (StateId, TokenId)> = HashMap::default();
+        let mut queue = VecDeque::from([self.initial_state]);
+        while let Some(state) = queue.pop_front() {
+            if state == *target {
+                let mut path = Vec::new();
+                let mut current = state;
+                while let Some(&(previous, token_id)) = predecessors.get(&current) {
+                    path.push(token_id);
+                    current = previous;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            let Some(map) = self.transitions.get(&state) else {
+                continue;
+            };
+            // Sorted token ids keep the found path deterministic.
+            let mut row: Vec<(&TokenId, &StateId)> = map.iter().collect();
+            row.sort_unstable();
+            for (token_id, next_state) in row {
+                if *token_id == self.eos_token_id
+                    || *next_state == self.initial_state
+                    || predecessors.contains_key(next_state)
+                {
+                    continue;
+                }
+                predecessors.insert(*next_state, (state, *token_id));
+                queue.push_back(*next_state);
+            }
         }
-        for (token, token_id) in [
-            (vec![32, 240, 159, 152], 7),
-            (vec![32, 240, 159, 152, 141], 6),
-            (vec![240, 159, 152, 141], 4),
-        ] {
-            vocabulary
-                .try_insert(token, token_id as u32)
-                .expect("Insert failed");
+        None
+    }
+
+    /// Returns, for every state able to reach a final state, the minimum number of
+    /// transitions needed to reach one. Final states have a distance of zero.
+    pub fn distances_to_final(&self) -> HashMap<StateId, usize> {
+        let mut predecessors: HashMap<StateId, Vec<StateId>> = HashMap::default();
+        for (state, token_id, next_state) in self.transitions_iter() {
+            if token_id != self.eos_token_id {
+                predecessors.entry(next_state).or_default().push(state);
+            }
         }
 
-        let index = Index::new(regex, &vocabulary).expect("Index failed");
-        assert_eq!(index.final_states(), &HashSet::from_iter([208, 128]));
+        let mut distances: HashMap<StateId, usize> =
+            self.final_states.iter().map(|state| (*state, 0)).collect();
+        let mut queue: VecDeque<StateId> = self.final_states.iter().copied().collect();
+        while let Some(state) = queue.pop_front() {
+            let distance = distances[&state] + 1;
+            for previous in predecessors.get(&state).into_iter().flatten() {
+                if !distances.contains_key(previous) {
+                    distances.insert(*previous, distance);
+                    queue.push_back(*previous);
+                }
+            }
+        }
+        distances
+    }
 
-        let expected = HashMap::from_iter([
-            (
-                208,
-                HashMap::from_iter([(3, 208), (8, 208), (4, 208), (2, 208)]),
-            ),
-            (
-                80,
-                HashMap::from_iter([(2, 128), (7, 192), (5, 208), (6, 208)]),
-            ),
-            (128, HashMap::from_iter([(8, 128)])),
-        ]);
-        assert_eq!(index.transitions(), &expected);
+    /// Returns a copy of the index without states unreachable from the initial state.
+    pub fn prune_unreachable(&self) -> Index {
+        let reachable = self.reachable_states();
+        Index {
+            initial_state: self.initial_state,
+            final_states: self
+                .final_states
+                .iter()
+                .filter(|state| reachable.contains(state))
+                .copied()
+                .collect(),
+            transitions: self
+                .transitions
+                .iter()
+                .filter(|(state, _)| reachable.contains(state))
+                .map(|(state, map)| (*state, map.clone()))
+                .collect(),
+            eos_token_id: self.eos_token_id,
+            vocab_size: self.vocab_size,
+            dfa_fingerprint: self.dfa_fingerprint,
+            states: HashSet::default(),
+        }
+        .with_collected_states()
+    }
+
+    /// Returns a copy of the index, which accepts only sequences of at most `max_tokens` tokens,
+    /// EOS token aside, so the length of generation is bounded structurally.
+    ///
+    /// The automaton is unrolled: every state of the copy is a pair of the original state and
+    /// the number of tokens taken to reach it, so the copy may have up to `max_tokens + 1` times
+    /// more states, numbered anew. Transitions are kept only if a final state is still reachable
+    /// within the remaining tokens. Fails with [`Error::EmptyLanguage`] if no accepted sequence
+    /// is short enough.
+    pub fn with_max_tokens(&self, max_tokens: usize) -> Result<Index> {
+        let distances = self.distances_to_final();
+        let fits = |state: &StateId, depth: usize| {
+            distances
+                .get(state)
+                .is_some_and(|distance| depth + distance <= max_tokens)
+        };
+        if !fits(&self.initial_state, 0) {
+            return Err(Error::EmptyLanguage);
+        }
+
+        let mut index = Index {
+            initial_state: 0,
+            final_states: HashSet::default(),
+            transitions: HashMap::default(),
+            eos_token_id: self.eos_token_id,
+            vocab_size: self.vocab_size,
+            dfa_fingerprint: None,
+            states: HashSet::default(),
+        };
+        let mut ids: HashMap<(StateId, usize), StateId> =
+            HashMap::from_iter([((self.initial_state, 0), 0)]);
+        let mut queue = VecDeque::from([(self.initial_state, 0)]);
+        while let Some((state, depth)) = queue.pop_front() {
+            let id = ids[&(state, depth)];
+            if self.is_final_state(&state) {
+                index.final_states.insert(id);
+            }
+            let mut map: HashMap<TokenId, StateId> = HashMap::default();
+            for (token_id, next_state) in self.transitions.get(&state).into_iter().flatten() {
+                if *token_id == self.eos_token_id {
+                    if *next_state == state {
+                        map.insert(*token_id, id);
+                    }
+                    continue;
+                }
+                if !fits(next_state, depth + 1) {
+                    continue;
+                }
+                let next_id = ids.len() as StateId;
+                let next_id = *ids.entry((*next_state, depth + 1)).or_insert_with(|| {
+                    queue.push_back((*next_state, depth + 1));
+                    next_id
+                });
+                map.insert(*token_id, next_id);
+            }
+            if !map.is_empty() {
+                index.transitions.insert(id, map);
+            }
+        }
+        Ok(index.with_collected_states())
+    }
+
+    /// Checks if the index has the same structure as `other`, regardless of state ids, e.g.
+    /// for indexes built from equivalent regular expressions.
+    ///
+    /// States reachable from the initial states are relabeled in breadth-first order, following
+    /// transitions in ascending order of token ids, so the indexes are isomorphic if relabeled
+    /// states, their finality and transitions with the same token ids are all the same.
+    /// Unreachable states are ignored.
+    pub fn is_isomorphic_to(&self, other: &Index) -> bool {
+        if self.eos_token_id != other.eos_token_id {
+            return false;
+        }
+        let mut labels: HashMap<StateId, usize> = HashMap::default();
+        let mut other_labels: HashMap<StateId, usize> = HashMap::default();
+        let mut queue = VecDeque::from([(self.initial_state, other.initial_state)]);
+        labels.insert(self.initial_state, 0);
+        other_labels.insert(other.initial_state, 0);
+
+        let sorted_row = |index: &Index, state: &StateId| -> Vec<(TokenId, StateId)> {
+            let mut row: Vec<(TokenId, StateId)> = index
+                .transitions
+                .get(state)
+                .into_iter()
+                .flatten()
+                .map(|(token_id, next_state)| (*token_id, *next_state))
+                .collect();
+            row.sort_unstable();
+            row
+        };
+
+        while let Some((state, other_state)) = queue.pop_front() {
+            if self.is_final_state(&state) != other.is_final_state(&other_state) {
+                return false;
+            }
+            let (row, other_row) = (sorted_row(self, &state), sorted_row(other, &other_state));
+            if row.len() != other_row.len() {
+                return false;
+            }
+            for ((token_id, next_state), (other_token_id, other_next_state)) in
+                row.into_iter().zip(other_row)
+            {
+                if token_id != other_token_id {
+                    return false;
+                }
+                let label = labels.len();
+                let next_label = *labels.entry(next_state).or_insert(label);
+                let other_label = other_labels.len();
+                let other_next_label = *other_labels.entry(other_next_state).or_insert(other_label);
+                if next_label != other_next_label {
+                    return false;
+                }
+                if next_label == label {
+                    queue.push_back((next_state, other_next_state));
+                }
+            }
+        }
+        true
+    }
+
+    /// Compares the index with `other` structurally.
+    ///
+    /// States are those having outgoing transitions or being final.
+    pub fn diff(&self, other: &Index) -> IndexDiff {
+        let states = |index: &Index| -> HashSet<StateId> {
+            index
+                .transitions
+                .keys()
+                .chain(index.final_states.iter())
+                .copied()
+                .collect()
+        };
+        let sorted_difference = |a: &HashSet<StateId>, b: &HashSet<StateId>| -> Vec<StateId> {
+            let mut difference: Vec<StateId> = a.difference(b).copied().collect();
+            difference.sort_unstable();
+            difference
+        };
+        let (states, other_states) = (states(self), states(other));
+
+        let mut diff = IndexDiff {
+            added_states: sorted_difference(&other_states, &states),
+            removed_states: sorted_difference(&states, &other_states),
+            added_final_states: sorted_difference(&other.final_states, &self.final_states),
+            removed_final_states: sorted_difference(&self.final_states, &other.final_states),
+            ..IndexDiff::default()
+        };
+        let lookup = |index: &Index, state: &StateId, token_id: &TokenId| -> Option<StateId> {
+            index.transitions.get(state)?.get(token_id).copied()
+        };
+        for (state, token_id, next_state) in self.transitions_iter() {
+            match lookup(other, &state, &token_id) {
+                None => diff.removed_transitions.push((state, token_id, next_state)),
+                Some(other_next_state) if other_next_state != next_state => diff
+                    .changed_transitions
+                    .push((state, token_id, next_state, other_next_state)),
+                Some(_) => {}
+            }
+        }
+        for (state, token_id, next_state) in other.transitions_iter() {
+            if lookup(self, &state, &token_id).is_none() {
+                diff.added_transitions.push((state, token_id, next_state));
+            }
+        }
+        diff.added_transitions.sort_unstable();
+        diff.removed_transitions.sort_unstable();
+        diff.changed_transitions.sort_unstable();
+        diff
+    }
+
+    /// Returns a stable hash of the index's contents.
+    ///
+    /// Equal indexes have the same fingerprint, regardless of the iteration order of
+    /// their internal maps, so it could be used as a persistent cache key.
+    pub fn fingerprint(&self) -> u64 {
+        let mut final_states: Vec<StateId> = self.final_states.iter().copied().collect();
+        final_states.sort_unstable();
+
+        let mut transitions: Vec<(StateId, TokenId, StateId)> = self.transitions_iter().collect();
+        transitions.sort_unstable();
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.initial_state.to_le_bytes());
+        bytes.extend_from_slice(&self.eos_token_id.to_le_bytes());
+        bytes.extend_from_slice(&(self.vocab_size as u64).to_le_bytes());
+        bytes.extend_from_slice(&(final_states.len() as u64).to_le_bytes());
+        for state in final_states {
+            bytes.extend_from_slice(&state.to_le_bytes());
+        }
+        for (state, token_id, next_state) in transitions {
+            bytes.extend_from_slice(&state.to_le_bytes());
+            bytes.extend_from_slice(&token_id.to_le_bytes());
+            bytes.extend_from_slice(&next_state.to_le_bytes());
+        }
+        fingerprint(&bytes)
+    }
+
+    /// Returns the transitions, formatted the same as `Display`, with states and token ids
+    /// in ascending order, so equal indexes always produce the same string.
+    pub fn to_sorted_string(&self) -> String {
+        self.to_string()
+    }
+
+    /// Returns the transitions as CSV, one `from_state,token_id,to_state,is_final` row
+    /// per transition after the header, in ascending order.
+    ///
+    /// `is_final` tells if `to_state` is a final state. EOS token self-loops of final
+    /// states are included as well.
+    pub fn to_csv(&self) -> String {
+        let mut transitions: Vec<(StateId, TokenId, StateId)> = self.transitions_iter().collect();
+        transitions.sort_unstable();
+
+        let mut csv = String::from("from_state,token_id,to_state,is_final\n");
+        for (state, token_id, next_state) in transitions {
+            let is_final = self.final_states.contains(&next_state);
+            csv.push_str(&format!("{state},{token_id},{next_state},{is_final}\n"));
+        }
+        csv
+    }
+
+    /// Returns the length, in transitions, of the longest path from the initial state
+    /// to any final state, or `None` if the transition graph contains a cycle and the
+    /// length is therefore unbounded.
+    ///
+    /// Self-loops of the EOS token on final states are not counted.
+    pub fn max_accepted_len(&self) -> Option<usize> {
+        // Longest distance to a final state for every fully explored state,
+        // `None` means no final state is reachable from it.
+        let mut longest: HashMap<StateId, Option<usize>> = HashMap::default();
+        let mut on_stack: HashSet<StateId> = HashSet::default();
+        let mut stack: Vec<(StateId, Vec<StateId>)> = Vec::new();
+
+        let successors = |state: &StateId| -> Vec<StateId> {
+            self.transitions
+                .get(state)
+                .map(|map| {
+                    map.iter()
+                        .filter(|(token_id, _)| **token_id != self.eos_token_id)
+                        .map(|(_, next_state)| *next_state)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        on_stack.insert(self.initial_state);
+        stack.push((self.initial_state, successors(&self.initial_state)));
+
+        while let Some((state, pending)) = stack.last_mut() {
+            if let Some(next_state) = pending.pop() {
+                if on_stack.contains(&next_state) {
+                    return None;
+                }
+                if !longest.contains_key(&next_state) {
+                    on_stack.insert(next_state);
+                    let next_successors = successors(&next_state);
+                    stack.push((next_state, next_successors));
+                }
+                continue;
+            }
+
+            let state = *state;
+            let mut best = self.is_final_state(&state).then_some(0);
+            if let Some(map) = self.transitions.get(&state) {
+                for (token_id, next_state) in map {
+                    if *token_id == self.eos_token_id {
+                        continue;
+                    }
+                    if let Some(Some(len)) = longest.get(next_state) {
+                        best = best.max(Some(len + 1));
+                    }
+                }
+            }
+            longest.insert(state, best);
+            on_stack.remove(&state);
+            stack.pop();
+        }
+
+        longest.get(&self.initial_state).copied().flatten()
+    }
+}
+
+impl std::fmt::Display for Index {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Index object with transitions:")?;
+        let mut state_ids: Vec<&StateId> = self.transitions.keys().collect();
+        state_ids.sort_unstable();
+        for state_id in state_ids {
+            let token_ids: BTreeMap<&TokenId, &StateId> =
+                self.transitions[state_id].iter().collect();
+            writeln!(f, "{:?} -> {:#?}", state_id, token_ids)?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes a stable hash of the DFA, which tells whether states of an index come from it.
+fn dfa_fingerprint(dfa: &DFA<Vec<u32>>) -> u64 {
+    let (bytes, padding) = dfa.to_bytes_little_endian();
+    fingerprint(&bytes[padding..])
+}
+
+/// Returns ids of tokens, which could start a match of the regular expression, in ascending
+/// order, i.e. their bytes keep a fresh DFA alive, without building the whole index.
+///
+/// Useful for debugging regular expressions, which no token of the vocabulary could start.
+/// EOS token is never included.
+pub fn tokens_matching_prefix(regex: &str, vocabulary: &Vocabulary) -> Result<Vec<TokenId>> {
+    let dfa = DFA::new(regex).map_err(Box::new)?;
+    let start_state = match dfa.universal_start_state(Anchored::Yes) {
+        Some(s) => s,
+        None => return Err(Error::DfaHasNoStartState),
+    };
+
+    let is_alive = |token: &[u8]| -> bool {
+        let mut state = start_state;
+        for byte in token {
+            state = dfa.next_state(state, *byte);
+            if dfa.is_dead_state(state) || dfa.is_quit_state(state) {
+                return false;
+            }
+        }
+        // Match states are delayed by a byte, so a match state right after the token
+        // means the match ended before its last byte, unless it also matches at the end.
+        !dfa.is_match_state(state) || dfa.is_match_state(dfa.next_eoi_state(state))
+    };
+
+    let mut token_ids: Vec<TokenId> = vocabulary
+        .tokens()
+        .iter()
+        .filter(|(token, _)| is_alive(token))
+        .flat_map(|(_, ids)| ids.iter().copied())
+        .filter(|token_id| *token_id != vocabulary.eos_token_id())
+        .collect();
+    token_ids.sort_unstable();
+    token_ids.dedup();
+    Ok(token_ids)
+}
+
+/// Compares tokens allowed at the initial states of two indexes, e.g. built from different
+/// regexes with the same vocabulary.
+///
+/// Returns tokens allowed only by `a` and tokens allowed only by `b`, both in ascending order.
+pub fn index_token_difference(a: &Index, b: &Index) -> (Vec<TokenId>, Vec<TokenId>) {
+    let allowed = |index: &Index| -> HashSet<TokenId> {
+        index
+            .allowed_tokens_iter(&index.initial_state())
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect()
+    };
+    let sorted_difference = |a: &HashSet<TokenId>, b: &HashSet<TokenId>| -> Vec<TokenId> {
+        let mut difference: Vec<TokenId> = a.difference(b).copied().collect();
+        difference.sort_unstable();
+        difference
+    };
+    let (allowed_a, allowed_b) = (allowed(a), allowed(b));
+    (
+        sorted_difference(&allowed_a, &allowed_b),
+        sorted_difference(&allowed_b, &allowed_a),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REGEX: &str = "0|[1-9][0-9]*";
+
+    fn vocabulary() -> Vocabulary {
+        let mut vocabulary = Vocabulary::new(4);
+        for (token, token_id) in [("blah", 0), ("1a", 1), ("2", 2), ("0", 3)] {
+            vocabulary
+                .try_insert(token, token_id)
+                .expect("Insert failed");
+        }
+        vocabulary
+    }
+
+    #[test]
+    fn display_is_sorted_and_stable() {
+        let regex = "[0-9]{1,3}";
+        let mut vocabulary = Vocabulary::new(10);
+        for token_id in (0..10).rev() {
+            vocabulary
+                .try_insert(token_id.to_string(), token_id)
+                .expect("Insert failed");
+        }
+        let index = Index::new(regex, &vocabulary).expect("Index failed");
+        let other = Index::new(regex, &vocabulary).expect("Index failed");
+
+        let output = index.to_sorted_string();
+        assert_eq!(output, other.to_string());
+
+        let states: Vec<StateId> = output
+            .lines()
+            .filter_map(|line| {
+                line.split_once(" -> ")
+                    .map(|(state, _)| state.parse().unwrap())
+            })
+            .collect();
+        let mut sorted_states = states.clone();
+        sorted_states.sort_unstable();
+        assert_eq!(states, sorted_states);
+        assert_eq!(states.len(), index.transitions().len());
+
+        let first_row = &index.transitions()[&states[0]];
+        let expected: BTreeMap<&TokenId, &StateId> = first_row.iter().collect();
+        assert!(output.contains(&format!("{:?} -> {:#?}", states[0], expected)));
+    }
+
+    #[test]
+    fn can_and_must_terminate() {
+        let vocabulary = vocabulary();
+        let index = Index::new(REGEX, &vocabulary).expect("Index failed");
+        let initial_state = index.initial_state();
+        assert!(!index.can_terminate(&initial_state));
+        assert!(!index.must_terminate(&initial_state));
+
+        // "2" is a number, which could be extended with more digits.
+        let continuable = index.next_state(&initial_state, &2).expect("Next state");
+        assert!(index.can_terminate(&continuable));
+        assert!(!index.must_terminate(&continuable));
+
+        // "0" can't be followed by anything.
+        let terminal = index.next_state(&initial_state, &3).expect("Next state");
+        assert!(index.can_terminate(&terminal));
+        assert!(index.must_terminate(&terminal));
+
+        assert!(!index.must_terminate(&StateId::MAX));
+    }
+
+    #[test]
+    fn index_from_external_dfa() {
+        use regex_automata::dfa::StartKind;
+
+        let vocabulary = vocabulary();
+
+        let dfa = DFA::builder().build(REGEX).expect("DFA failed");
+        let index = Index::from_dfa(&dfa, &vocabulary).expect("Index failed");
+        assert_eq!(index, Index::new(REGEX, &vocabulary).expect("Index failed"));
+
+        let dfa = DFA::builder()
+            .configure(DFA::config().start_kind(StartKind::Unanchored))
+            .build(REGEX)
+            .expect("DFA failed");
+        let result = Index::from_dfa(&dfa, &vocabulary);
+        assert!(matches!(result, Err(Error::DfaHasNoStartState)));
+    }
+
+    #[test]
+    fn empty_language_is_rejected() {
+        let mut vocabulary = Vocabulary::new(4);
+        for (token, token_id) in [("a", 0), ("b", 1)] {
+            vocabulary
+                .try_insert(token, token_id)
+                .expect("Insert failed");
+        }
+        for regex in ["a{2}b{3}[^\\s\\S]", "c+"] {
+            let result = Index::new(regex, &vocabulary);
+            assert!(matches!(result, Err(Error::EmptyLanguage)), "{regex}");
+        }
+        assert!(Index::new("a+b", &vocabulary).is_ok());
+    }
+
+    #[test]
+    fn index_with_flags() {
+        let mut vocabulary = Vocabulary::new(4);
+        for (token, token_id) in [("A", 0), ("bC", 1), ("\n", 2), ("abc", 3)] {
+            vocabulary
+                .try_insert(token, token_id)
+                .expect("Insert failed");
+        }
+        let accepts = |index: &Index, token_ids: &[TokenId]| {
+            token_ids
+                .iter()
+                .try_fold(index.initial_state(), |state, token_id| {
+                    index.next_state(&state, token_id)
+                })
+                .is_some_and(|state| index.is_final_state(&state))
+        };
+
+        let index = Index::new("abc", &vocabulary).expect("Index failed");
+        assert!(accepts(&index, &[3]));
+        assert!(!accepts(&index, &[0, 1]));
+
+        let flags = RegexFlags {
+            case_insensitive: true,
+            ..RegexFlags::default()
+        };
+        let index = Index::new_with_flags("abc", &vocabulary, flags).expect("Index failed");
+        assert!(accepts(&index, &[3]));
+        assert!(accepts(&index, &[0, 1]));
+
+        // Without flags no tokens' sequence completes a match.
+        let result = Index::new("a.bC", &vocabulary);
+        assert!(matches!(result, Err(Error::EmptyLanguage)));
+        let flags = RegexFlags {
+            case_insensitive: true,
+            dot_matches_new_line: true,
+        };
+        let index = Index::new_with_flags("a.bC", &vocabulary, flags).expect("Index failed");
+        assert!(accepts(&index, &[0, 2, 1]));
+    }
+
+    #[test]
+    fn terminating_tokens_of_state() {
+        let vocabulary = vocabulary();
+        let index = Index::new(REGEX, &vocabulary).expect("Index failed");
+        let initial_state = index.initial_state();
+        assert_eq!(index.terminating_tokens(&initial_state), vec![2, 3]);
+
+        // After "0" only EOS is allowed, which isn't a terminating token.
+        let terminal = index.next_state(&initial_state, &3).expect("Next state");
+        assert!(index.terminating_tokens(&terminal).is_empty());
+        assert!(index.terminating_tokens(&StateId::MAX).is_empty());
+    }
+
+    #[test]
+    fn prune_unreachable_states() {
+        let vocabulary = vocabulary();
+        let index = Index::new(REGEX, &vocabulary).expect("Index failed");
+        let reachable = index.reachable_states();
+        assert_eq!(reachable, HashSet::from_iter([24, 40, 48, 56]));
+
+        let mut injected = index.clone();
+        injected
+            .transitions
+            .insert(1000, HashMap::from_iter([(2, 24), (4, 1000)]));
+        injected.final_states.insert(1000);
+        assert_eq!(injected.reachable_states(), reachable);
+
+        let pruned = injected.prune_unreachable();
+        assert!(!pruned.transitions().contains_key(&1000));
+        assert!(!pruned.is_final_state(&1000));
+        assert_eq!(pruned, index);
+
+        let accepts = |index: &Index, token_ids: &[TokenId]| {
+            token_ids
+                .iter()
+                .try_fold(index.initial_state(), |state, token_id| {
+                    index.next_state(&state, token_id)
+                })
+                .is_some_and(|state| index.is_final_state(&state))
+        };
+        for token_ids in [&[3][..], &[2, 2, 3], &[3, 2], &[0], &[]] {
+            assert_eq!(accepts(&pruned, token_ids), accepts(&injected, token_ids));
+        }
+    }
+
+    #[test]
+    fn diff_of_modified_clone() {
+        let vocabulary = vocabulary();
+        let index = Index::new(REGEX, &vocabulary).expect("Index failed");
+        assert!(index.diff(&index.clone()).is_empty());
+
+        let mut modified = index.clone();
+        let initial_state = index.initial_state();
+        let next_state = index.next_state(&initial_state, &2).expect("No transition");
+        modified
+            .transitions
+            .get_mut(&initial_state)
+            .expect("No transitions")
+            .insert(2, initial_state);
+
+        let diff = index.diff(&modified);
+        assert_eq!(
+            diff,
+            IndexDiff {
+                changed_transitions: vec![(initial_state, 2, next_state, initial_state)],
+                ..IndexDiff::default()
+            }
+        );
+
+        modified.transitions.remove(&24);
+        modified.final_states.remove(&24);
+        let diff = index.diff(&modified);
+        assert_eq!(diff.removed_states, vec![24]);
+        assert_eq!(diff.removed_final_states, vec![24]);
+        assert_eq!(diff.removed_transitions.len(), index.transitions[&24].len());
+        assert!(diff.added_transitions.is_empty());
+        assert_eq!(modified.diff(&index).added_states, vec![24]);
+    }
+
+    #[test]
+    fn distances_to_final_states() {
+        let vocabulary = vocabulary();
+        let index = Index::new(REGEX, &vocabulary).expect("Index failed");
+        let distances = index.distances_to_final();
+
+        assert_eq!(distances[&index.initial_state()], 1);
+        for state in index.final_states() {
+            assert_eq!(distances[state], 0);
+        }
+        assert_eq!(distances.len(), index.reachable_states().len());
+
+        let mut injected = index.clone();
+        injected
+            .transitions
+            .insert(1000, HashMap::from_iter([(2, 1001)]));
+        assert!(!injected.distances_to_final().contains_key(&1000));
+    }
+
+    #[test]
+    fn shortest_token_path_to_state() {
+        let vocabulary = vocabulary();
+        let index = Index::new(REGEX, &vocabulary).expect("Index failed");
+        let initial_state = index.initial_state();
+        assert_eq!(index.shortest_token_path(&initial_state), Some(vec![]));
+
+        // State of a number with two digits at least, where the lowest token ids are taken.
+        assert_eq!(index.shortest_token_path(&24), Some(vec![2, 2]));
+        for &target in index.final_states() {
+            let path = index.shortest_token_path(&target).expect("Path not found");
+            let state = path
+                .iter()
+                .try_fold(initial_state, |state, token_id| {
+                    index.next_state(&state, token_id)
+                })
+                .expect("Path is not accepted");
+            assert_eq!(state, target);
+            assert!(index.is_final_state(&state));
+        }
+
+        assert_eq!(index.shortest_token_path(&StateId::MAX), None);
+    }
+
+    #[test]
+    fn union_of_regexes() {
+        let mut vocabulary = Vocabulary::new(5);
+        for (token, token_id) in [("a", 0), ("b", 1), ("1", 2), ("2", 3), ("ab", 4)] {
+            vocabulary
+                .try_insert(token, token_id)
+                .expect("Insert failed");
+        }
+        let index = Index::union(&["a+b", "[0-9]"], &vocabulary).expect("Index failed");
+
+        let accepts = |token_ids: &[TokenId]| {
+            token_ids
+                .iter()
+                .try_fold(index.initial_state(), |state, token_id| {
+                    index.next_state(&state, token_id)
+                })
+                .is_some_and(|state| index.is_final_state(&state))
+        };
+        for token_ids in [&[0, 1][..], &[0, 0, 1], &[4], &[0, 4], &[2], &[3]] {
+            assert!(accepts(token_ids), "{:?}", token_ids);
+        }
+        for token_ids in [&[0][..], &[1], &[2, 3], &[0, 2], &[4, 1], &[]] {
+            assert!(!accepts(token_ids), "{:?}", token_ids);
+        }
+    }
+
+    #[test]
+    fn anchors_are_stripped_with_warnings() {
+        let mut vocabulary = Vocabulary::new(3);
+        for (token, token_id) in [("0", 0), ("1", 1), ("$", 2)] {
+            vocabulary
+                .try_insert(token, token_id)
+                .expect("Insert failed");
+        }
+        let expected = Index::new("0", &vocabulary).expect("Index failed");
+
+        let (index, warnings) = Index::new_with_warnings("^0$", &vocabulary).expect("Index failed");
+        assert_eq!(
+            warnings,
+            vec![Warning::StartAnchorStripped, Warning::EndAnchorStripped]
+        );
+        assert_eq!(index, expected);
+
+        let (index, warnings) = Index::new_with_warnings("0", &vocabulary).expect("Index failed");
+        assert!(warnings.is_empty());
+        assert_eq!(index, expected);
+
+        let (index, warnings) =
+            Index::new_with_warnings(r"0\$", &vocabulary).expect("Index failed");
+        assert!(warnings.is_empty());
+        let state = index
+            .next_state(&index.initial_state(), &0)
+            .expect("Next state");
+        let state = index.next_state(&state, &2).expect("Next state");
+        assert!(index.is_final_state(&state));
+    }
+
+    #[test]
+    fn index_from_regex() {
+        let eos_token_id = 4;
+        let mut vocabulary = Vocabulary::new(eos_token_id);
+        for (token, token_id) in [("blah", 0), ("1a", 1), ("2", 2), ("0", 3)] {
+            vocabulary
+                .try_insert(token, token_id as u32)
+                .expect("Insert failed");
+        }
+        let index = Index::new(REGEX, &vocabulary).expect("Index failed");
+        let initial_state = index.initial_state();
+        assert_eq!(initial_state, 40);
+        assert_eq!(index.final_states(), &HashSet::from_iter([24, 48, 56]));
+        assert!(!index.is_final_state(&initial_state));
+
+        let expected = HashMap::from_iter([
+            (24, HashMap::from_iter([(3, 24), (4, 24), (2, 24)])),
+            (48, HashMap::from_iter([(4, 48)])),
+            (40, HashMap::from_iter([(3, 48), (2, 56)])),
+            (56, HashMap::from_iter([(3, 24), (4, 56), (2, 24)])),
+        ]);
+        assert_eq!(index.transitions(), &expected);
+
+        let allowed_tokens = index
+            .allowed_tokens(&initial_state)
+            .expect("No allowed tokens");
+        let token_id = allowed_tokens.first().expect("No first tokens");
+
+        let state = 48;
+        assert_eq!(index.next_state(&initial_state, token_id), Some(state));
+        assert!(index.is_final_state(&state));
+
+        assert_eq!(index.next_state(&state, &eos_token_id), None);
+        assert_eq!(index.next_state(&state, token_id), None);
+    }
+
+    #[test]
+    fn index_from_regex_initital_in_allowed() {
+        let regex = "`\\n(\\.\\n)?`\\n";
+        let mut vocabulary = Vocabulary::new(104);
+        for (token, token_id) in [("\n", 103), (".", 102), ("`", 101)] {
+            vocabulary
+                .try_insert(token, token_id as u32)
+                .expect("Insert failed");
+        }
+
+        let index = Index::new(regex, &vocabulary).expect("Index failed");
+        let allowed = index
+            .allowed_tokens(&index.initial_state())
+            .expect("No allowed tokens");
+        assert!(allowed.contains(&101));
+    }
+
+    #[test]
+    fn index_from_regex_multibyte() {
+        let regex = "😇| [😈-😍][😇-😎]*";
+        let mut vocabulary = Vocabulary::new(8);
+        for (token, token_id) in [(" 😍", 5), ("blah", 0), ("😇", 2), ("😈a", 1), ("😍", 3)]
+        {
+            vocabulary
+                .try_insert(token, token_id as u32)
+                .expect("Insert failed");
+        }
+        for (token, token_id) in [
+            (vec![32, 240, 159, 152], 7),
+            (vec![32, 240, 159, 152, 141], 6),
+            (vec![240, 159, 152, 141], 4),
+        ] {
+            vocabulary
+                .try_insert(token, token_id as u32)
+                .expect("Insert failed");
+        }
+
+        let index = Index::new(regex, &vocabulary).expect("Index failed");
+        assert_eq!(index.final_states(), &HashSet::from_iter([208, 128]));
+
+        let expected = HashMap::from_iter([
+            (
+                208,
+                HashMap::from_iter([(3, 208), (8, 208), (4, 208), (2, 208)]),
+            ),
+            (
+                80,
+                HashMap::from_iter([(2, 128), (7, 192), (5, 208), (6, 208)]),
+            ),
+            (128, HashMap::from_iter([(8, 128)])),
+        ]);
+        assert_eq!(index.transitions(), &expected);
+    }
+
+    #[test]
+    fn fingerprint_of_equal_indexes() {
+        let mut vocabulary = vocabulary();
+        let index = Index::new(REGEX, &vocabulary).expect("Index failed");
+        let other = Index::new(REGEX, &vocabulary).expect("Index failed");
+        assert_eq!(index.fingerprint(), other.fingerprint());
+        assert_eq!(index.fingerprint(), index.clone().fingerprint());
+
+        vocabulary.try_insert("5", 5).expect("Insert failed");
+        let modified = Index::new(REGEX, &vocabulary).expect("Index failed");
+        assert_ne!(index.fingerprint(), modified.fingerprint());
+    }
+
+    #[test]
+    fn write_and_read_round_trip() {
+        use std::io::Cursor;
+
+        let vocabulary = vocabulary();
+        let index = Index::new(REGEX, &vocabulary).expect("Index failed");
+
+        let mut buffer = Cursor::new(Vec::new());
+        index.write_to(&mut buffer).expect("Write failed");
+        buffer.set_position(0);
+        let restored = Index::read_from(&mut buffer).expect("Read failed");
+        assert_eq!(index, restored);
+
+        match Index::read_from(Cursor::new(vec![1, 2, 3])) {
+            Err(Error::DecodeError(_)) => {}
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn transitions_iter_matches_map() {
+        let vocabulary = vocabulary();
+        let index = Index::new(REGEX, &vocabulary).expect("Index failed");
+
+        let mut rebuilt: HashMap<StateId, HashMap<TokenId, StateId>> = HashMap::default();
+        for (state, token_id, next_state) in index.transitions_iter() {
+            rebuilt
+                .entry(state)
+                .or_default()
+                .insert(token_id, next_state);
+        }
+        assert_eq!(&rebuilt, index.transitions());
+    }
+
+    #[test]
+    fn new_many_matches_individual_indexes() {
+        let first = vocabulary();
+        let mut second = Vocabulary::new(5);
+        for (token, token_id) in [("1", 0), ("12", 1), ("0", 2), ("a", 3), ("9", 4)] {
+            second
+                .try_insert(token, token_id as u32)
+                .expect("Insert failed");
+        }
+
+        let indexes = Index::new_many(REGEX, &[&first, &second]).expect("Indexes failed");
+        assert_eq!(
+            indexes,
+            vec![
+                Index::new(REGEX, &first).expect("Index failed"),
+                Index::new(REGEX, &second).expect("Index failed"),
+            ]
+        );
+        assert!(Index::new_many(REGEX, &[])
+            .expect("Indexes failed")
+            .is_empty());
+    }
+
+    #[test]
+    fn add_tokens_matches_rebuilt_index() {
+        let mut vocabulary = Vocabulary::new(4);
+        for (token, token_id) in [("blah", 0), ("0", 3)] {
+            vocabulary
+                .try_insert(token, token_id as u32)
+                .expect("Insert failed");
+        }
+        let mut index = Index::new(REGEX, &vocabulary).expect("Index failed");
+
+        let new_tokens: Vec<(Token, TokenId)> = vec![(b"1a".to_vec(), 1), (b"2".to_vec(), 2)];
+        for (token, token_id) in &new_tokens {
+            vocabulary
+                .try_insert(token.clone(), *token_id)
+                .expect("Insert failed");
+        }
+        index
+            .add_tokens(REGEX, &vocabulary, &new_tokens)
+            .expect("Add tokens failed");
+
+        let rebuilt = Index::new(REGEX, &vocabulary).expect("Index failed");
+        assert_eq!(index, rebuilt);
+
+        match index.add_tokens(REGEX, &vocabulary, &[(b"3".to_vec(), 4)]) {
+            Err(Error::EOSTokenDisallowed) => {}
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn add_tokens_from_other_regex() {
+        let (regex, other_regex) = ("[0-9]+", "[a-z]+");
+        let mut vocabulary = Vocabulary::new(3);
+        for (token, token_id) in [("1", 0), ("a", 1)] {
+            vocabulary
+                .try_insert(token, token_id as u32)
+                .expect("Insert failed");
+        }
+        let mut index = Index::new(regex, &vocabulary).expect("Index failed");
+        let other = Index::new(other_regex, &vocabulary).expect("Index failed");
+        assert_eq!(index.initial_state(), other.initial_state());
+
+        vocabulary.try_insert("b", 2).expect("Insert failed");
+        match index.add_tokens(other_regex, &vocabulary, &[(b"b".to_vec(), 2)]) {
+            Err(Error::IndexRegexMismatch) => {}
+            _ => unreachable!(),
+        }
+
+        let dfa = DFA::new(regex).expect("DFA failed");
+        index
+            .add_tokens_from_dfa(&dfa, &vocabulary, &[(b"b".to_vec(), 2)])
+            .expect("Add tokens failed");
+        assert_eq!(index, Index::new(regex, &vocabulary).expect("Index failed"));
+
+        let mut renumbered = index.with_max_tokens(2).expect("Index failed");
+        match renumbered.add_tokens(regex, &vocabulary, &[]) {
+            Err(Error::IndexRegexMismatch) => {}
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn max_accepted_len_bounded() {
+        let regex = "abc";
+        let mut vocabulary = Vocabulary::new(4);
+        for (token, token_id) in [("a", 0), ("b", 1), ("c", 2), ("ab", 3)] {
+            vocabulary
+                .try_insert(token, token_id as u32)
+                .expect("Insert failed");
+        }
+
+        let index = Index::new(regex, &vocabulary).expect("Index failed");
+        assert_eq!(index.max_accepted_len(), Some(3));
+    }
+
+    #[test]
+    fn max_accepted_len_unbounded() {
+        let vocabulary = vocabulary();
+
+        let index = Index::new(REGEX, &vocabulary).expect("Index failed");
+        assert_eq!(index.max_accepted_len(), None);
+    }
+
+    #[test]
+    fn initial_state_is_final() {
+        let mut vocabulary = Vocabulary::new(2);
+        vocabulary.try_insert("a", 0).expect("Insert failed");
+        vocabulary.try_insert("b", 1).expect("Insert failed");
+
+        let index = Index::new("a?", &vocabulary).expect("Index failed");
+        assert!(index.initial_is_final());
+        assert!(index.is_final_state(&index.initial_state()));
+        let allowed = index
+            .allowed_tokens(&index.initial_state())
+            .expect("No allowed tokens");
+        assert!(allowed.contains(&2));
+
+        let index = Index::new("a", &vocabulary).expect("Index failed");
+        assert!(!index.initial_is_final());
+    }
+
+    #[test]
+    fn contains_valid_and_bogus_states() {
+        let mut vocabulary = Vocabulary::new(3);
+        for (token, token_id) in [("a", 0), ("b", 1), ("c", 2)] {
+            vocabulary
+                .try_insert(token, token_id)
+                .expect("Insert failed");
+        }
+        let index = Index::new("ab?c", &vocabulary).expect("Index failed");
+
+        let initial_state = index.initial_state();
+        assert!(index.contains_state(&initial_state));
+        for (state, token_id, next_state) in index.transitions_iter() {
+            assert!(index.contains_state(&state));
+            assert!(index.contains_state(&next_state));
+            assert_eq!(
+                index
+                    .checked_next_state(&state, &token_id)
+                    .expect("State is known"),
+                index.next_state(&state, &token_id)
+            );
+        }
+        for state in index.final_states() {
+            assert!(index.contains_state(state));
+        }
+
+        let bogus = StateId::MAX;
+        assert!(!index.contains_state(&bogus));
+        assert_eq!(index.next_state(&bogus, &0), None);
+        assert!(matches!(
+            index.checked_next_state(&bogus, &0),
+            Err(Error::UnknownState { state }) if state == bogus
+        ));
+        assert_eq!(
+            index
+                .checked_next_state(&initial_state, &2)
+                .expect("State is known"),
+            None
+        );
+
+        // Dead end state, which is neither final nor has transitions of its own.
+        let mut vocabulary = Vocabulary::new(2);
+        for (token, token_id) in [("a", 0), ("c", 1)] {
+            vocabulary
+                .try_insert(token, token_id)
+                .expect("Insert failed");
+        }
+        let index = Index::new("ab|c", &vocabulary).expect("Index failed");
+        let dead_end = index
+            .next_state(&index.initial_state(), &0)
+            .expect("Token is allowed");
+        assert!(index.allowed_tokens(&dead_end).is_none());
+        assert!(index.contains_state(&dead_end));
+    }
+
+    #[test]
+    fn token_difference_at_initial_state() {
+        let mut vocabulary = Vocabulary::new(4);
+        for (token, token_id) in [("a", 0), ("1", 1), ("b", 2), ("2a", 3)] {
+            vocabulary
+                .try_insert(token, token_id)
+                .expect("Insert failed");
+        }
+        let letters = Index::new("[a-z]+", &vocabulary).expect("Index failed");
+        let alphanumeric = Index::new("[0-9a]+", &vocabulary).expect("Index failed");
+
+        assert_eq!(
+            index_token_difference(&letters, &alphanumeric),
+            (vec![2], vec![1, 3])
+        );
+        assert_eq!(index_token_difference(&letters, &letters), (vec![], vec![]));
+    }
+
+    #[test]
+    fn token_id_out_of_range() {
+        let mut vocabulary = Vocabulary::new(2);
+        vocabulary.try_insert("a", 0).expect("Insert failed");
+        vocabulary.try_insert("b", 1).expect("Insert failed");
+        vocabulary.try_insert("bb", 31).expect("Insert failed");
+        assert_eq!(Index::max_token_id_bound(vocabulary.len()), 32);
+        let index = Index::new("[ab]+", &vocabulary).expect("Index failed");
+        assert!(index.validate_token_ids().is_ok());
+
+        vocabulary.try_insert("ab", 100).expect("Insert failed");
+        let index = Index::new("[ab]+", &vocabulary).expect("Index failed");
+        assert!(matches!(
+            index.validate_token_ids(),
+            Err(Error::TokenIdOutOfRange {
+                id: 100,
+                vocab_size: 5
+            })
+        ));
+    }
+
+    #[test]
+    fn transitions_as_csv() {
+        let mut vocabulary = Vocabulary::new(3);
+        for (token, token_id) in [("a", 0), ("b", 1), ("ab", 2)] {
+            vocabulary
+                .try_insert(token, token_id)
+                .expect("Insert failed");
+        }
+        let index = Index::new("ab", &vocabulary).expect("Index failed");
+        let csv = index.to_csv();
+
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("from_state,token_id,to_state,is_final"));
+        let rows: Vec<&str> = lines.collect();
+        assert_eq!(rows.len(), index.transitions_iter().count());
+
+        let initial_state = index.initial_state();
+        let final_state = index
+            .next_state(&initial_state, &2)
+            .expect("Token is allowed");
+        assert!(rows.contains(&format!("{initial_state},2,{final_state},true").as_str()));
+        assert!(rows.contains(&format!("{final_state},3,{final_state},true").as_str()));
+    }
+
+    #[test]
+    fn walk_token_sequence() {
+        let vocabulary = vocabulary();
+        let index = Index::new(REGEX, &vocabulary).expect("Index failed");
+
+        let states: Vec<Option<StateId>> = index.walk(&[2, 3, 2]).collect();
+        assert_eq!(states, vec![Some(56), Some(24), Some(24)]);
+
+        // Leading zero isn't followed by digits, walk ends after the first invalid token.
+        let states: Vec<Option<StateId>> = index.walk(&[3, 2, 3]).collect();
+        assert_eq!(states, vec![Some(48), None]);
+
+        assert_eq!(index.walk(&[]).count(), 0);
+    }
+
+    #[test]
+    fn project_vocabulary_to_used_tokens() {
+        let vocabulary = vocabulary();
+        let index = Index::new(REGEX, &vocabulary).expect("Index failed");
+        let used = index.used_token_ids();
+        assert_eq!(used, HashSet::from_iter([2, 3]));
+
+        let projected = vocabulary.project_to(&used);
+        assert_eq!(projected.token_ids("blah"), None);
+        assert_eq!(projected.token_ids("1a"), None);
+        assert_eq!(projected.token_ids("2"), Some(&vec![2]));
+        assert_eq!(projected.token_ids("0"), Some(&vec![3]));
+        assert_eq!(projected.eos_token_id(), 4);
+        assert_eq!(projected.len(), 3);
+
+        let projected_index = Index::new(REGEX, &projected).expect("Index failed");
+        assert_eq!(projected_index.transitions(), index.transitions());
+    }
+
+    #[test]
+    fn index_without_eos_self_loops() {
+        let vocabulary = vocabulary();
+        let config = IndexConfig {
+            add_eos_self_loops: false,
+        };
+        let index = Index::new_with_config(REGEX, &vocabulary, config).expect("Index failed");
+        let default = Index::new(REGEX, &vocabulary).expect("Index failed");
+        assert_eq!(index.final_states(), default.final_states());
+
+        for state in index.final_states() {
+            assert!(index.is_final_state(state));
+            let allowed = index.allowed_tokens(state).expect("No allowed tokens");
+            assert!(!allowed.contains(&4));
+            assert!(default
+                .allowed_tokens(state)
+                .expect("No allowed tokens")
+                .contains(&4));
+        }
+        // State after "0" allows nothing else, but still must terminate.
+        assert_eq!(index.allowed_tokens(&48), Some(vec![]));
+        assert!(index.must_terminate(&48));
+        assert_eq!(index.next_state(&40, &2), default.next_state(&40, &2));
+    }
+
+    #[test]
+    fn num_states_and_transitions() {
+        let vocabulary = vocabulary();
+        let index = Index::new(REGEX, &vocabulary).expect("Index failed");
+        // States 24, 40, 48 and 56, see `index_from_regex`.
+        assert_eq!(index.num_states(), 4);
+        assert_eq!(index.num_transitions(), 9);
+    }
+
+    #[test]
+    fn index_from_schema_with_regex() {
+        let schema =
+            r#"{"type": "object", "properties": {"a": {"type": "integer"}}, "required": ["a"]}"#;
+        let mut vocabulary = Vocabulary::new(6);
+        for (token, token_id) in [
+            ("{", 0),
+            ("\"a\"", 1),
+            (":", 2),
+            ("1", 3),
+            ("2", 4),
+            ("}", 5),
+        ] {
+            vocabulary
+                .try_insert(token, token_id)
+                .expect("Insert failed");
+        }
+        let options = GenerationOptions {
+            whitespace_pattern: Some(String::new()),
+            max_recursion_depth: Some(1),
+            ..GenerationOptions::default()
+        };
+        let (index, regex) =
+            Index::new_from_schema_with_regex(schema, &vocabulary, &options).expect("Index failed");
+        let json_value = serde_json::from_str(schema).expect("Invalid schema");
+        assert_eq!(
+            regex,
+            json_schema::regex_from_value_with_options(&json_value, &options)
+                .expect("To regex failed")
+        );
+        assert_eq!(
+            Index::new_from_schema(schema, &vocabulary, &options).expect("Index failed"),
+            index
+        );
+
+        let states: Vec<Option<StateId>> = index.walk(&[0, 1, 2, 3, 4, 5]).collect();
+        let last = states.last().copied().flatten().expect("Path is allowed");
+        assert!(states.iter().all(Option::is_some));
+        assert!(index.is_final_state(&last));
+        assert_eq!(index.walk(&[0, 5]).last(), Some(None));
+    }
+
+    #[test]
+    fn startable_tokens_and_matching_prefixes() {
+        let mut vocabulary = Vocabulary::new(4);
+        for (token, token_id) in [("blah", 0), ("1a", 1), ("2", 2), ("0", 3), ("00", 5)] {
+            vocabulary
+                .try_insert(token, token_id)
+                .expect("Insert failed");
+        }
+        let index = Index::new(REGEX, &vocabulary).expect("Index failed");
+        assert_eq!(index.startable_tokens(), vec![2, 3]);
+        assert_eq!(
+            tokens_matching_prefix(REGEX, &vocabulary).expect("Prefixes failed"),
+            vec![2, 3]
+        );
+        // Tokens may only start a match, which isn't complete yet.
+        assert_eq!(
+            tokens_matching_prefix("1abc|0", &vocabulary).expect("Prefixes failed"),
+            vec![1, 3]
+        );
+        assert!(tokens_matching_prefix("x", &vocabulary)
+            .expect("Prefixes failed")
+            .is_empty());
+    }
+
+    #[test]
+    fn isomorphic_indexes() {
+        let tokens = [("blah", 0), ("1a", 1), ("2", 2), ("0", 3)];
+        let mut vocabulary = Vocabulary::new(4);
+        let mut reversed = Vocabulary::new(4);
+        for (token, token_id) in tokens {
+            vocabulary
+                .try_insert(token, token_id)
+                .expect("Insert failed");
+        }
+        for (token, token_id) in tokens.into_iter().rev() {
+            reversed.try_insert(token, token_id).expect("Insert failed");
+        }
+        let index = Index::new(REGEX, &vocabulary).expect("Index failed");
+        let other = Index::new(REGEX, &reversed).expect("Index failed");
+        assert!(index.is_isomorphic_to(&other));
+
+        // Same structure with different state ids.
+        let relabel = |state: &StateId| state + 1000;
+        let relabeled = Index {
+            initial_state: relabel(&index.initial_state),
+            final_states: index.final_states.iter().map(relabel).collect(),
+            transitions: index
+                .transitions
+                .iter()
+                .map(|(state, map)| {
+                    let map = map
+                        .iter()
+                        .map(|(token_id, next_state)| (*token_id, relabel(next_state)))
+                        .collect();
+                    (relabel(state), map)
+                })
+                .collect(),
+            eos_token_id: index.eos_token_id,
+            vocab_size: index.vocab_size,
+            dfa_fingerprint: None,
+            states: HashSet::default(),
+        }
+        .with_collected_states();
+        assert_ne!(index, relabeled);
+        assert!(index.is_isomorphic_to(&relabeled));
+        assert!(relabeled.is_isomorphic_to(&index));
+
+        let different = Index::new("0|[1-9]", &vocabulary).expect("Index failed");
+        assert!(!index.is_isomorphic_to(&different));
+    }
+
+    #[test]
+    fn index_with_max_tokens() {
+        let vocabulary = vocabulary();
+        let index = Index::new(REGEX, &vocabulary).expect("Index failed");
+        let capped = index.with_max_tokens(2).expect("Capped index failed");
+
+        let accepts = |index: &Index, tokens: &[TokenId]| {
+            let states: Vec<Option<StateId>> = index.walk(tokens).collect();
+            states.len() == tokens.len()
+                && states
+                    .last()
+                    .copied()
+                    .flatten()
+                    .is_some_and(|state| index.is_final_state(&state))
+        };
+        for tokens in [&[3][..], &[2], &[2, 2], &[2, 3]] {
+            assert!(accepts(&index, tokens));
+            assert!(accepts(&capped, tokens), "{tokens:?}");
+        }
+        for tokens in [&[2, 2, 2][..], &[2, 3, 3, 2]] {
+            assert!(accepts(&index, tokens));
+            assert!(!accepts(&capped, tokens), "{tokens:?}");
+        }
+
+        // Final states keep EOS, the last one allows nothing else.
+        let last = capped
+            .walk(&[2, 2])
+            .last()
+            .flatten()
+            .expect("Path is allowed");
+        assert_eq!(capped.allowed_tokens(&last), Some(vec![4]));
+        assert!(capped.must_terminate(&last));
+
+        assert!(matches!(
+            index.with_max_tokens(0),
+            Err(Error::EmptyLanguage)
+        ));
     }
 }
@@ -0,0 +1,157 @@
+//! Byte-level guidance of raw text generation by a regular expression.
+
+use regex_automata::dfa::dense::DFA;
+use regex_automata::dfa::Automaton;
+use regex_automata::util::primitives::StateID as AutomataStateId;
+use regex_automata::Anchored;
+use rustc_hash::FxHashSet as HashSet;
+
+use crate::json_schema::validator::{build_dfa, is_rejecting_state};
+use crate::primitives::StateId;
+use crate::{Error, Result};
+
+/// `ByteGuide` tells which bytes could be generated next to keep matching a regular expression.
+///
+/// It complements token-level [`Index`](crate::index::Index) for byte by byte generation.
+/// States passed to its methods are expected to be obtained from the same `ByteGuide`, other
+/// states are neither final nor allow any bytes.
+///
+/// ## Example
+/// ```rust
+/// # use outlines_core::Error;
+/// use outlines_core::json_schema::ByteGuide;
+///
+/// # fn main() -> Result<(), Error> {
+/// let guide = ByteGuide::new("ab|ac")?;
+/// let state = guide.initial_state();
+/// assert_eq!(guide.allowed_bytes(state), vec![b'a']);
+///
+/// let state = guide.next_byte_state(state, b'a').expect("Byte is allowed");
+/// assert_eq!(guide.allowed_bytes(state), vec![b'b', b'c']);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ByteGuide {
+    dfa: DFA<Vec<u32>>,
+    initial_state: AutomataStateId,
+    /// All states reachable from the initial state, to tell apart states of other guides.
+    states: HashSet<AutomataStateId>,
+}
+
+impl ByteGuide {
+    /// Builds a `ByteGuide` from a regular expression.
+    pub fn new(regex: &str) -> Result<Self> {
+        let dfa = build_dfa(regex)?;
+        let initial_state = match dfa.universal_start_state(Anchored::Yes) {
+            Some(s) => s,
+            None => return Err(Error::DfaHasNoStartState),
+        };
+
+        let mut states = HashSet::from_iter([initial_state]);
+        let mut next_states = vec![initial_state];
+        while let Some(state) = next_states.pop() {
+            for byte in 0..=u8::MAX {
+                let next_state = dfa.next_state(state, byte);
+                if !is_rejecting_state(&dfa, next_state) && states.insert(next_state) {
+                    next_states.push(next_state);
+                }
+            }
+        }
+        Ok(Self {
+            dfa,
+            initial_state,
+            states,
+        })
+    }
+
+    /// Returns the ID of the initial state.
+    pub fn initial_state(&self) -> StateId {
+        self.initial_state.as_u32()
+    }
+
+    /// Checks if the text generated up to the state fully matches the regular expression.
+    pub fn is_final_state(&self, state: StateId) -> bool {
+        self.automata_state(state)
+            .is_some_and(|state| self.dfa.is_match_state(self.dfa.next_eoi_state(state)))
+    }
+
+    /// Returns the next state after the byte, or `None` if the byte breaks the match.
+    pub fn next_byte_state(&self, state: StateId, byte: u8) -> Option<StateId> {
+        let next_state = self.dfa.next_state(self.automata_state(state)?, byte);
+        (!is_rejecting_state(&self.dfa, next_state)).then(|| next_state.as_u32())
+    }
+
+    /// Lists bytes, which keep the match alive from the state, in ascending order.
+    pub fn allowed_bytes(&self, state: StateId) -> Vec<u8> {
+        (0..=u8::MAX)
+            .filter(|byte| self.next_byte_state(state, *byte).is_some())
+            .collect()
+    }
+
+    /// Returns the automaton state, if the state belongs to the guide.
+    fn automata_state(&self, state: StateId) -> Option<AutomataStateId> {
+        let state = AutomataStateId::new(state as usize).ok()?;
+        self.states.contains(&state).then_some(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowed_bytes_per_step() {
+        let guide = ByteGuide::new("abc").expect("ByteGuide failed");
+        let mut state = guide.initial_state();
+        for byte in [b'a', b'b', b'c'] {
+            assert!(!guide.is_final_state(state));
+            assert_eq!(guide.allowed_bytes(state), vec![byte]);
+            assert_eq!(guide.next_byte_state(state, b'x'), None);
+            state = guide.next_byte_state(state, byte).expect("Byte is allowed");
+        }
+        assert!(guide.is_final_state(state));
+        assert!(guide.allowed_bytes(state).is_empty());
+    }
+
+    #[test]
+    fn allowed_bytes_with_repetition() {
+        let guide = ByteGuide::new("[0-9]+").expect("ByteGuide failed");
+        let digits: Vec<u8> = (b'0'..=b'9').collect();
+        let state = guide.initial_state();
+        assert_eq!(guide.allowed_bytes(state), digits);
+
+        let state = guide.next_byte_state(state, b'4').expect("Byte is allowed");
+        assert!(guide.is_final_state(state));
+        assert_eq!(guide.allowed_bytes(state), digits);
+    }
+
+    #[test]
+    fn decimal_number() {
+        let regex = crate::json_schema::regex_from_str(r#"{"type": "number"}"#, None, None)
+            .expect("To regex failed");
+        let guide = ByteGuide::new(&regex).expect("ByteGuide failed");
+        let mut state = guide.initial_state();
+        for byte in b"1.5" {
+            assert!(guide.allowed_bytes(state).contains(byte));
+            state = guide
+                .next_byte_state(state, *byte)
+                .expect("Byte is allowed");
+        }
+        assert!(guide.is_final_state(state));
+    }
+
+    #[test]
+    fn foreign_states() {
+        let guide = ByteGuide::new("abc").expect("ByteGuide failed");
+        let other = ByteGuide::new("[0-9]+").expect("ByteGuide failed");
+        let state = other
+            .next_byte_state(other.initial_state(), b'1')
+            .expect("Byte is allowed");
+        for state in [state, u32::MAX] {
+            assert!(!guide.is_final_state(state));
+            assert_eq!(guide.next_byte_state(state, b'a'), None);
+            assert!(guide.allowed_bytes(state).is_empty());
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! Normalizes equivalent JSON schemas and their regexes into the same form.
+
+use regex_syntax::hir::{Hir, HirKind};
+use serde_json::{Map, Value};
+
+use crate::json_schema::parsing::ANNOTATION_KEYWORDS;
+use crate::json_schema::{regex_from_value_with_options, GenerationOptions};
+use crate::Result;
+
+/// Keywords, whose value is a map of names to sub-schemas.
+const SCHEMA_MAP_KEYWORDS: [&str; 4] = ["properties", "$defs", "definitions", "patternProperties"];
+
+/// Keywords, whose value is an array of sub-schemas.
+const SCHEMA_ARRAY_KEYWORDS: [&str; 4] = ["allOf", "anyOf", "oneOf", "prefixItems"];
+
+/// Keywords, whose value is a sub-schema.
+const SCHEMA_KEYWORDS: [&str; 4] = ["items", "additionalProperties", "propertyNames", "not"];
+
+/// Returns a canonical form of the schema, so equivalent schemas, which differ only in form,
+/// produce the same regex and the same cache keys.
+///
+/// Within every schema and sub-schema:
+/// - keys are sorted,
+/// - annotations, like `title` or `description`, are dropped,
+/// - `type` array of one element is replaced by the element itself,
+/// - `enum` of one member is replaced by `const`.
+///
+/// Names within `properties` keep their order, since it defines the order of properties
+/// in the generated regex, and values of `const` and `enum` are left intact.
+///
+/// # Example
+///
+/// ```rust
+/// use serde_json::json;
+/// use outlines_core::json_schema::canonicalize;
+///
+/// let schema = json!({"title": "Size", "type": ["string"], "enum": ["S"]});
+/// assert_eq!(canonicalize(&schema), json!({"const": "S", "type": "string"}));
+/// ```
+pub fn canonicalize(value: &Value) -> Value {
+    let Value::Object(obj) = value else {
+        return value.clone();
+    };
+
+    let mut entries: Vec<(String, Value)> = Vec::with_capacity(obj.len());
+    for (key, value) in obj {
+        let key = key.as_str();
+        if ANNOTATION_KEYWORDS.contains(&key) {
+            continue;
+        }
+        let value = match (key, value) {
+            ("type", Value::Array(types)) if types.len() == 1 => types[0].clone(),
+            ("enum", Value::Array(members)) if members.len() == 1 && !obj.contains_key("const") => {
+                entries.push((String::from("const"), members[0].clone()));
+                continue;
+            }
+            (key, Value::Object(schemas)) if SCHEMA_MAP_KEYWORDS.contains(&key) => Value::Object(
+                schemas
+                    .iter()
+                    .map(|(name, schema)| (name.clone(), canonicalize(schema)))
+                    .collect(),
+            ),
+            (key, Value::Array(schemas)) if SCHEMA_ARRAY_KEYWORDS.contains(&key) => {
+                Value::Array(schemas.iter().map(canonicalize).collect())
+            }
+            (key, schema) if SCHEMA_KEYWORDS.contains(&key) => canonicalize(schema),
+            (_, value) => value.clone(),
+        };
+        entries.push((key.to_string(), value));
+    }
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Value::Object(entries.into_iter().collect::<Map<String, Value>>())
+}
+
+/// Generates a canonical regex of the schema, so equivalent schemas, which differ only in form,
+/// produce the same regex string, e.g. to deduplicate or cache indexes.
+///
+/// The schema is [`canonicalize`]d first, then the generated regex is rewritten:
+/// - groups are non-capturing and only kept, where they are needed,
+/// - nested alternations and concatenations are flattened, common prefixes of alternatives
+///   are factored out and alternatives of single characters are merged into classes,
+/// - alternatives, which are plain literals none of which is a prefix of another, like
+///   members of a string `enum`, are sorted. Order of other alternatives is kept, since
+///   it defines the priority of matches.
+///
+/// The canonical regex matches the same strings, but could differ from the one generated
+/// by [`regex_from_value_with_options`] textually.
+///
+/// # Example
+///
+/// ```rust
+/// use outlines_core::json_schema::{canonical_regex, GenerationOptions};
+///
+/// let options = GenerationOptions::default();
+/// let a = canonical_regex(r#"{"type": "string", "enum": ["b", "a"]}"#, &options).unwrap();
+/// let b = canonical_regex(r#"{"enum": ["a", "b"], "type": "string"}"#, &options).unwrap();
+/// assert_eq!(a, b);
+/// ```
+pub fn canonical_regex(schema: &str, options: &GenerationOptions) -> Result<String> {
+    let json_value: Value = serde_json::from_str(schema)?;
+    let options = GenerationOptions {
+        non_capturing_groups: true,
+        ..options.clone()
+    };
+    let regex = regex_from_value_with_options(&canonicalize(&json_value), &options)?;
+    let hir = regex_syntax::parse(&regex).map_err(Box::new)?;
+    Ok(canonical_hir(hir).to_string())
+}
+
+/// Rebuilds the regex tree without captures and with sorted alternatives, where it's sound.
+fn canonical_hir(hir: Hir) -> Hir {
+    match hir.into_kind() {
+        HirKind::Empty => Hir::empty(),
+        HirKind::Literal(literal) => Hir::literal(literal.0),
+        HirKind::Class(class) => Hir::class(class),
+        HirKind::Look(look) => Hir::look(look),
+        HirKind::Repetition(mut repetition) => {
+            repetition.sub = Box::new(canonical_hir(*repetition.sub));
+            Hir::repetition(repetition)
+        }
+        HirKind::Capture(capture) => canonical_hir(*capture.sub),
+        HirKind::Concat(subs) => Hir::concat(subs.into_iter().map(canonical_hir).collect()),
+        HirKind::Alternation(subs) => {
+            let mut subs: Vec<Hir> = subs.into_iter().map(canonical_hir).collect();
+            if let Some(literals) = prefix_free_literals(&subs) {
+                subs = literals.into_iter().map(Hir::literal).collect();
+            }
+            Hir::alternation(subs)
+        }
+    }
+}
+
+/// Returns sorted bytes of alternatives, if all of them are literals and none of them is
+/// a prefix of another, so at most one could match at any position and their order doesn't
+/// matter.
+fn prefix_free_literals(subs: &[Hir]) -> Option<Vec<Box<[u8]>>> {
+    let mut literals = subs
+        .iter()
+        .map(|sub| match sub.kind() {
+            HirKind::Literal(literal) => Some(literal.0.clone()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    literals.sort_unstable();
+    // Once sorted, a prefix of a literal would be right before it.
+    let prefix_free = literals
+        .windows(2)
+        .all(|pair| !pair[1].starts_with(&pair[0]));
+    prefix_free.then_some(literals)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn equivalent_schemas_are_equal() {
+        let a = json!({
+            "type": "object",
+            "title": "Shirt",
+            "properties": {
+                "size": {"type": ["string"], "enum": ["M"], "description": "Size"},
+                "title": {"type": "string"}
+            },
+            "required": ["size"]
+        });
+        let b = json!({
+            "required": ["size"],
+            "properties": {
+                "size": {"const": "M", "type": "string"},
+                "title": {"examples": ["Shirt"], "type": ["string"]}
+            },
+            "type": "object"
+        });
+        assert_eq!(canonicalize(&a), canonicalize(&b));
+        assert_eq!(canonicalize(&a).to_string(), canonicalize(&b).to_string());
+        assert_eq!(canonicalize(&canonicalize(&a)), canonicalize(&a));
+    }
+
+    #[test]
+    fn property_order_and_data_are_kept() {
+        let schema = json!({
+            "properties": {"b": {"type": "integer"}, "a": {"type": "integer"}},
+            "const": {"z": 1, "title": 2},
+            "enum": [1, 2]
+        });
+        let canonical = canonicalize(&schema);
+        let names: Vec<&String> = canonical["properties"]
+            .as_object()
+            .expect("Properties")
+            .keys()
+            .collect();
+        assert_eq!(names, ["b", "a"]);
+        assert_eq!(canonical["const"].to_string(), r#"{"z":1,"title":2}"#);
+        assert_eq!(canonical["enum"], json!([1, 2]));
+        assert_ne!(
+            canonical.to_string(),
+            canonicalize(&json!({
+                "properties": {"a": {"type": "integer"}, "b": {"type": "integer"}},
+                "const": {"z": 1, "title": 2},
+                "enum": [1, 2]
+            }))
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn reordered_schemas_have_same_canonical_regex() {
+        let a = r#"{
+            "type": "object",
+            "properties": {
+                "size": {"enum": ["M", "S", "XL"], "type": "string"},
+                "count": {"type": "integer", "title": "Count"}
+            },
+            "required": ["size"]
+        }"#;
+        let b = r#"{
+            "required": ["size"],
+            "properties": {
+                "size": {"type": "string", "enum": ["XL", "M", "S"]},
+                "count": {"type": ["integer"]}
+            },
+            "type": "object"
+        }"#;
+        let options = GenerationOptions::default();
+        let regex = |schema: &str| {
+            let json_value: Value = serde_json::from_str(schema).expect("Invalid schema");
+            regex_from_value_with_options(&json_value, &options).expect("To regex failed")
+        };
+        assert_ne!(regex(a), regex(b));
+
+        let canonical = canonical_regex(a, &options).expect("Canonical regex failed");
+        assert_eq!(
+            canonical,
+            canonical_regex(b, &options).expect("Canonical regex failed")
+        );
+        let re = regex::Regex::new(&format!("^(?:{canonical})$")).expect("Regex failed");
+        for value in [r#"{"size": "XL"}"#, r#"{"size": "S", "count": 2}"#] {
+            assert!(re.is_match(value), "{value}");
+            assert!(regex::Regex::new(&format!("^(?:{})$", regex(a)))
+                .expect("Regex failed")
+                .is_match(value));
+        }
+        assert!(!re.is_match(r#"{"size": "L"}"#));
+    }
+
+    #[test]
+    fn alternatives_with_prefixes_keep_order() {
+        let options = GenerationOptions::default();
+        let a = canonical_regex(r#"{"type": "string", "pattern": "ab|a|c"}"#, &options)
+            .expect("Canonical regex failed");
+        let b = canonical_regex(r#"{"type": "string", "pattern": "a|ab|c"}"#, &options)
+            .expect("Canonical regex failed");
+        assert_ne!(a, b);
+    }
+}
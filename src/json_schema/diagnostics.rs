@@ -0,0 +1,226 @@
+//! Locates the parts of a JSON schema, which fail regex generation or aren't honored by it.
+
+use once_cell::sync::Lazy;
+use serde_json::Value;
+
+use crate::json_schema::parsing::ANNOTATION_KEYWORDS;
+use crate::json_schema::{parser, GenerationOptions};
+use crate::Error;
+
+/// Keywords honored by regex generation, besides annotations.
+const SUPPORTED_KEYWORDS: [&str; 41] = [
+    // Common
+    "type",
+    "enum",
+    "const",
+    // Object
+    "properties",
+    "required",
+    "additionalProperties",
+    "minProperties",
+    "maxProperties",
+    "propertyNames",
+    // Array
+    "items",
+    "prefixItems",
+    "minItems",
+    "maxItems",
+    // String
+    "minLength",
+    "maxLength",
+    "pattern",
+    "format",
+    "contentEncoding",
+    // Number and integer
+    "minimum",
+    "maximum",
+    "exclusiveMinimum",
+    "exclusiveMaximum",
+    // Number
+    "minDigitsInteger",
+    "maxDigitsInteger",
+    "minDigitsFraction",
+    "maxDigitsFraction",
+    "minDigitsExponent",
+    "maxDigitsExponent",
+    // Integer
+    "minDigits",
+    "maxDigits",
+    // Logical
+    "allOf",
+    "anyOf",
+    "oneOf",
+    // References
+    "$ref",
+    "$defs",
+    "definitions",
+    "$id",
+    "$schema",
+    "$anchor",
+    "$dynamicAnchor",
+    // Non-standard depth of unconstrained objects and arrays
+    "depth",
+];
+
+static KEYWORDS: Lazy<Vec<&'static str>> = Lazy::new(|| {
+    SUPPORTED_KEYWORDS
+        .iter()
+        .chain(ANNOTATION_KEYWORDS.iter())
+        .copied()
+        .collect()
+});
+
+/// Returns keywords of JSON schema honored by regex generation, including annotations,
+/// which are accepted, but don't affect generated regex.
+pub fn supported_keywords() -> &'static [&'static str] {
+    &KEYWORDS
+}
+
+/// Returns JSON pointers to keywords of the schema and its sub-schemas, which aren't honored
+/// by regex generation, e.g. to check a schema before generation.
+///
+/// Sub-schemas are descended into only through supported keywords, while an unsupported
+/// keyword is reported once, without its content.
+///
+/// # Example
+///
+/// ```rust
+/// use serde_json::json;
+/// use outlines_core::json_schema::unsupported_keywords_in;
+///
+/// let schema = json!({"type": "array", "items": {"type": "integer"}, "uniqueItems": true});
+/// assert_eq!(unsupported_keywords_in(&schema), vec!["/uniqueItems"]);
+/// ```
+pub fn unsupported_keywords_in(value: &Value) -> Vec<String> {
+    let mut unsupported = Vec::new();
+    collect_unsupported_keywords(value, "", &mut unsupported);
+    unsupported
+}
+
+fn collect_unsupported_keywords(schema: &Value, pointer: &str, unsupported: &mut Vec<String>) {
+    let Value::Object(obj) = schema else {
+        return;
+    };
+
+    for (key, value) in obj {
+        let key_pointer = format!("{pointer}/{}", escape_token(key));
+        if !supported_keywords().contains(&key.as_str()) {
+            unsupported.push(key_pointer);
+            continue;
+        }
+        match (key.as_str(), value) {
+            ("properties" | "$defs" | "definitions", Value::Object(sub_schemas)) => {
+                for (name, sub_schema) in sub_schemas {
+                    let sub_pointer = format!("{key_pointer}/{}", escape_token(name));
+                    collect_unsupported_keywords(sub_schema, &sub_pointer, unsupported);
+                }
+            }
+            ("items" | "additionalProperties" | "propertyNames", Value::Object(_)) => {
+                collect_unsupported_keywords(value, &key_pointer, unsupported);
+            }
+            ("items" | "prefixItems" | "allOf" | "anyOf" | "oneOf", Value::Array(sub_schemas)) => {
+                for (i, sub_schema) in sub_schemas.iter().enumerate() {
+                    let sub_pointer = format!("{key_pointer}/{i}");
+                    collect_unsupported_keywords(sub_schema, &sub_pointer, unsupported);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns JSON pointer to the innermost sub-schema of `root`, which fails regex generation.
+///
+/// Sub-schemas are descended into while they keep failing on their own, local references
+/// are followed to their definitions.
+pub(crate) fn locate_error(root: &Value, options: &GenerationOptions) -> String {
+    let mut pointer = String::new();
+    let mut schema = root;
+    let mut visited = vec![pointer.clone()];
+
+    'descend: loop {
+        for (child_pointer, child) in sub_schemas(root, schema, &pointer) {
+            if visited.contains(&child_pointer) {
+                continue;
+            }
+            if parser(root, options).to_regex(child).is_err() {
+                visited.push(child_pointer.clone());
+                pointer = child_pointer;
+                schema = child;
+                continue 'descend;
+            }
+        }
+        return pointer;
+    }
+}
+
+/// Replaces the failing sub-schema, which could be huge, in the error with its keywords,
+/// since the sub-schema is pointed to anyway.
+///
+/// Only unsupported keywords are kept, unless there are none, e.g. when a required keyword,
+/// like `type`, is missing.
+pub(crate) fn localize_error(error: Error) -> Error {
+    let Error::UnsupportedJsonSchema(schema) = error else {
+        return error;
+    };
+    let Value::Object(obj) = schema.as_ref() else {
+        return Error::UnsupportedJsonSchema(schema);
+    };
+    let keywords: Vec<&String> = obj.keys().collect();
+    let unsupported: Vec<&String> = keywords
+        .iter()
+        .copied()
+        .filter(|key| !supported_keywords().contains(&key.as_str()))
+        .collect();
+    let keywords = if unsupported.is_empty() {
+        keywords
+    } else {
+        unsupported
+    };
+    Error::UnsupportedKeywords(
+        keywords
+            .into_iter()
+            .map(|key| Box::from(key.as_str()))
+            .collect(),
+    )
+}
+
+fn sub_schemas<'a>(root: &'a Value, schema: &'a Value, pointer: &str) -> Vec<(String, &'a Value)> {
+    let Value::Object(obj) = schema else {
+        return vec![];
+    };
+
+    let mut sub_schemas = Vec::new();
+    for (key, value) in obj {
+        match (key.as_str(), value) {
+            ("properties", Value::Object(properties)) => {
+                for (name, sub_schema) in properties {
+                    let sub_pointer = format!("{pointer}/properties/{}", escape_token(name));
+                    sub_schemas.push((sub_pointer, sub_schema));
+                }
+            }
+            ("items" | "additionalProperties" | "propertyNames", Value::Object(_)) => {
+                sub_schemas.push((format!("{pointer}/{key}"), value));
+            }
+            ("items" | "prefixItems" | "allOf" | "anyOf" | "oneOf", Value::Array(items)) => {
+                for (i, sub_schema) in items.iter().enumerate() {
+                    sub_schemas.push((format!("{pointer}/{key}/{i}"), sub_schema));
+                }
+            }
+            ("$ref", Value::String(reference)) => {
+                if let Some(fragment) = reference.strip_prefix('#') {
+                    if let Some(sub_schema) = root.pointer(fragment) {
+                        sub_schemas.push((fragment.to_string(), sub_schema));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    sub_schemas
+}
+
+/// Escapes a reference token of JSON pointer, as defined by RFC 6901.
+fn escape_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
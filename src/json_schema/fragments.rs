@@ -0,0 +1,105 @@
+//! Building blocks of regular expressions for custom compositions.
+//!
+//! Fragments follow the conventions of regexes generated from JSON schemas: keys are escaped
+//! JSON strings, values are grouped and whitespace is allowed where [`WHITESPACE`] is, unless
+//! a custom whitespace pattern is given. Values could be any patterns, e.g. from [`types`].
+//!
+//! # Example
+//!
+//! ```rust
+//! use outlines_core::json_schema::{fragments, INTEGER, STRING};
+//!
+//! let tags = fragments::array(STRING, Some(1), None, None);
+//! let regex = fragments::object(&[("id", INTEGER), ("tags", &tags)], None);
+//! assert!(regex::Regex::new(&format!("^{regex}$"))
+//!     .expect("Regex failed")
+//!     .is_match(r#"{"id": 1, "tags": ["a", "b"]}"#));
+//! ```
+//!
+//! [`types`]: crate::json_schema::types
+
+use serde_json::Value;
+
+use crate::json_schema::parsing::Parser;
+use crate::json_schema::WHITESPACE;
+
+/// Returns the pattern of an object key, i.e. the escaped JSON string of `name`.
+pub fn key(name: &str) -> String {
+    regex::escape(&Value::String(name.to_string()).to_string())
+}
+
+/// Returns the pattern of an object with exactly the given properties, in the given order,
+/// where each property is a pair of its name and its value pattern.
+pub fn object(properties: &[(&str, &str)], whitespace: Option<&str>) -> String {
+    let whitespace = whitespace.unwrap_or(WHITESPACE);
+    let properties: Vec<String> = properties
+        .iter()
+        .map(|(name, value)| format!("{0}{1}:{1}({value})", key(name), whitespace))
+        .collect();
+    format!(
+        r"\{{{0}{1}{0}\}}",
+        whitespace,
+        properties.join(&format!("{0},{0}", whitespace))
+    )
+}
+
+/// Returns the pattern of an array of items matching `item` pattern, with optional bounds
+/// on the number of items.
+pub fn array(
+    item: &str,
+    min_items: Option<u64>,
+    max_items: Option<u64>,
+    whitespace: Option<&str>,
+) -> String {
+    let whitespace = whitespace.unwrap_or(WHITESPACE);
+    let Some(num_repeats) = Parser::get_num_items_pattern(min_items, max_items) else {
+        return format!(r"\[{0}\]", whitespace);
+    };
+    let allow_empty = if min_items.unwrap_or(0) == 0 { "?" } else { "" };
+    format!(
+        r"\[{whitespace}(({item})(,{whitespace}({item})){num_repeats}){allow_empty}{whitespace}\]"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::*;
+    use crate::json_schema::{BOOLEAN, INTEGER, STRING};
+
+    fn is_full_match(regex: &str, value: &str) -> bool {
+        Regex::new(&format!("^(?:{regex})$"))
+            .expect("Regex failed")
+            .is_match(value)
+    }
+
+    #[test]
+    fn object_fragment() {
+        let regex = object(&[("name", STRING), ("a.b", INTEGER)], None);
+        assert!(is_full_match(&regex, r#"{"name": "x", "a.b": 1}"#));
+        assert!(is_full_match(&regex, r#"{"name":"x","a.b":-2}"#));
+        assert!(!is_full_match(&regex, r#"{"name": "x", "aXb": 1}"#));
+        assert!(!is_full_match(&regex, r#"{"a.b": 1, "name": "x"}"#));
+        assert!(!is_full_match(&regex, r#"{"name": "x"}"#));
+
+        let regex = object(&[("ok", BOOLEAN)], Some(r"[\n ]*"));
+        assert!(is_full_match(&regex, "{\n  \"ok\": true\n}"));
+        assert!(is_full_match(&object(&[], None), "{ }"));
+    }
+
+    #[test]
+    fn array_fragment() {
+        let regex = array(INTEGER, Some(1), Some(2), None);
+        assert!(is_full_match(&regex, "[1]"));
+        assert!(is_full_match(&regex, "[1, 2]"));
+        assert!(!is_full_match(&regex, "[]"));
+        assert!(!is_full_match(&regex, "[1, 2, 3]"));
+        assert!(!is_full_match(&regex, r#"["1"]"#));
+
+        let regex = array(STRING, None, None, None);
+        assert!(is_full_match(&regex, "[]"));
+        assert!(is_full_match(&regex, r#"["a", "b", "c"]"#));
+        assert!(is_full_match(&array(STRING, None, Some(0), None), "[]"));
+    }
+}
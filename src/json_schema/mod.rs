@@ -15,28 +15,41 @@
 //! #### Common
 //!  - `type`
 //!     - Specifies the data type (string, number, integer, boolean, array, object, null).
+//!     - Non-standard `float` and `decimal` are accepted as aliases of `number`.
 //!  - `enum`
 //!     - Lists the allowed values.
+//!     - Duplicated values are alternated once, in the order of their first appearance,
+//!       unless [`GenerationOptions::sort_enum`] is set.
 //!  - `const`
 //!     - Specifies a single allowed value.
 //!
 //! #### Object
 //! - `properties`
 //!     - Defines the expected properties of an object and their schemas.
+//!     - Properties are expected in the declared order, unless [`PropertyOrder::AnyOrder`] is set.
 //! - `required`
 //!     - Lists the properties that must be present.
 //! - `additionalProperties`
 //!     - Specifies whether additional properties are allowed or defines their schema.
+//!     - Along with `properties`, additional properties are allowed after the declared ones only
+//!       if it's `true` or a schema, and they aren't counted by `minProperties` or `maxProperties`.
 //! - `minProperties`
 //!     - Minimum number of properties required.
 //! - `maxProperties`
-//!     - Maximum number of properties allowed.
+//!     - Maximum number of properties allowed, `0` allows only the empty object.
+//!     - Along with `properties`, both bounds are enforced by enumerating combinations of optional
+//!       properties, which is combinatorial, so it's supported for up to 10 optional properties.
+//! - `propertyNames`
+//!     - Defines the string schema, which names of properties must match, when there are
+//!       no `properties`. Both `minProperties` and `maxProperties` are enforced along with it.
 //!
 //! #### Array
 //! - `items`
 //!     - Defines the schema for array elements (single schema or a schema per index).
 //! - `prefixItems`
 //!     - Specifies schemas for the first few elements of an array (tuple validation).
+//!     - Followed by elements of the `items` schema, if any, otherwise the array is exactly
+//!       as long as the prefix. `minItems` and `maxItems` count the prefix too.
 //! - `minItems`
 //!     - Minimum number of items required in the array.
 //! - `maxItems`
@@ -49,8 +62,22 @@
 //!     - Maximum string length.
 //! - `pattern`
 //!     - Regular expression the string must match.
+//!     - Combined with `minLength` and `maxLength` only when the pattern is a single repeated atom,
+//!       like `^[a-z]+$`, otherwise an error is returned.
 //! - `format`
 //!     - Specifies a pre-defined format, these are supported [`FormatType`]
+//! - `contentEncoding`
+//!     - `base64` restricts the string to the padded base64 alphabet, same as `byte` format,
+//!       unless `pattern`, length bounds or `format` are given. Other encodings are ignored.
+//!
+//! #### Number and Integer
+//! - `minimum`, `maximum`, `exclusiveMinimum`, `exclusiveMaximum`
+//!     - Bounds are over-approximated, since ranges of decimals can't be matched exactly:
+//!       a non-negative minimum forbids the minus sign, a negative maximum requires it, and
+//!       the magnitude of the bounds caps the number of digits in the integer part, e.g.
+//!       `maximum` of 1.0 allows 1.5, and `maximum` of 150 allows 999. Exponents aren't
+//!       restricted, so `maximum` of 100 allows `1e+1`, but also `9e+9`. Exclusive bounds are
+//!       treated as inclusive.
 //!
 //! #### Number
 //! - `minDigitsInteger`
@@ -79,6 +106,8 @@
 //!     - Combines multiple schemas; at least one must be valid.
 //! - `oneOf`
 //!     - Combines multiple schemas; exactly one must be valid.
+//!     - Exclusivity isn't enforced, branches are alternated like `anyOf`, unless
+//!       [`GenerationOptions::strict_one_of`] is set.
 //!
 //! ### Recursion
 //!
@@ -93,19 +122,68 @@
 //!
 //! ### References
 //!
-//! Only local references are currently being supported.
+//! Only local references are currently being supported, either JSON pointers, like `#/$defs/a`,
+//! or names declared by `$anchor` and `$dynamicAnchor`, like `#a`.
+//!
+//! ### Annotations
+//!
+//! Annotation keywords `title`, `description`, `readOnly`, `writeOnly`, `default`, `examples`,
+//! `deprecated`, `$comment` and `contentMediaType` are accepted, but ignored for regex generation.
+//! A schema made of annotations only, possibly along with `contentEncoding`, is unconstrained.
 //!
 //! ### Unconstrained objects
 //!
 //! An empty object means unconstrained, allowing any JSON type.
+//!
+//! Boolean schemas are supported as well: `true` is unconstrained, same as an empty object,
+//! and `false` allows nothing, so a property with the `false` schema could never be present.
+//!
+//! ## Errors
+//!
+//! Failures of regex generation are reported with the crate's [`Error`](crate::Error),
+//! there is no separate error type for JSON schema parsing.
+//!
+//! Keywords, which aren't honored, could be found before generation with
+//! [`unsupported_keywords_in`], and all honored ones are listed by [`supported_keywords`].
+//!
+//! ## Validation
+//!
+//! Generated text could be checked incrementally against a regular expression with [`Validator`].
+//!
+//! Generation could be guided byte by byte, rather than token by token, with [`ByteGuide`].
+//!
+//! Schema's `examples` and `default` could be checked against its regex with [`check_examples`],
+//! or the `default` alone with [`default_matches`].
+//!
+//! ## Fragments
+//!
+//! Regexes of common constructs, like objects and arrays, could be composed of custom patterns
+//! with the same conventions as generated ones, see [`fragments`].
+//!
+//! ## Canonicalization
+//!
+//! Schemas differing only in form, like key order or annotations, could be brought to the same
+//! form with [`canonicalize`], e.g. to be used as a cache key. [`canonical_regex`] goes further
+//! and rewrites the generated regex into a canonical form as well.
 
+pub use byte_guide::ByteGuide;
+pub use canonical::{canonical_regex, canonicalize};
+pub use diagnostics::{supported_keywords, unsupported_keywords_in};
 use serde_json::Value;
 pub use types::*;
+pub use validator::{FeedResult, Validator};
 
+pub mod byte_guide;
+mod canonical;
+mod diagnostics;
+pub mod fragments;
 mod parsing;
 pub mod types;
+pub mod validator;
 
-use crate::Result;
+use crate::index::Index;
+use crate::vocabulary::Vocabulary;
+use crate::{Error, Result};
 
 /// Generates a regular expression string from given JSON schema string.
 ///
@@ -187,21 +265,252 @@ pub fn regex_from_value(
     whitespace_pattern: Option<&str>,
     max_recursion_depth: Option<usize>,
 ) -> Result<String> {
-    let mut parser = parsing::Parser::new(json);
-    if let Some(pattern) = whitespace_pattern {
+    let options = GenerationOptions {
+        whitespace_pattern: whitespace_pattern.map(str::to_string),
+        max_recursion_depth,
+        ..GenerationOptions::default()
+    };
+    regex_from_value_with_options(json, &options)
+}
+
+/// Checks the schema's `examples` and top-level `default` against the regex generated from it.
+///
+/// Returns every checked value, serialized to JSON, along with whether it matched.
+///
+/// # Example
+///
+/// ```rust
+/// # use outlines_core::Error;
+/// use outlines_core::prelude::*;
+///
+/// # fn main() -> Result<(), Error> {
+///     let schema = r#"{"type": "integer", "examples": [1, "one"]}"#;
+///     let checked = json_schema::check_examples(schema)?;
+///     assert_eq!(checked, vec![("1".to_string(), true), (r#""one""#.to_string(), false)]);
+/// #   Ok(())
+/// }
+/// ```
+pub fn check_examples(schema: &str) -> Result<Vec<(String, bool)>> {
+    let json_value: Value = serde_json::from_str(schema)?;
+    let regex = regex_from_value(&json_value, None, None)?;
+    let mut validator = Validator::new(&regex)?;
+
+    let examples = json_value
+        .get("examples")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten();
+    let default = json_value.get("default");
+
+    examples
+        .chain(default)
+        .map(|value| {
+            let value = serde_json::to_string(value)?;
+            validator.reset();
+            let is_match = validator.feed(value.as_bytes()) == FeedResult::Accepting;
+            Ok((value, is_match))
+        })
+        .collect()
+}
+
+/// Checks whether the schema's top-level `default` matches the regex generated from it.
+///
+/// Returns `None` if the schema has no `default`.
+///
+/// # Example
+///
+/// ```rust
+/// # use outlines_core::Error;
+/// use outlines_core::prelude::*;
+///
+/// # fn main() -> Result<(), Error> {
+///     let schema = r#"{"type": "string", "maxLength": 3, "default": "four"}"#;
+///     assert_eq!(json_schema::default_matches(schema)?, Some(false));
+/// #   Ok(())
+/// }
+/// ```
+pub fn default_matches(schema: &str) -> Result<Option<bool>> {
+    let json_value: Value = serde_json::from_str(schema)?;
+    let Some(default) = json_value.get("default") else {
+        return Ok(None);
+    };
+    let regex = regex_from_value(&json_value, None, None)?;
+    let mut validator = Validator::new(&regex)?;
+    let default = serde_json::to_string(default)?;
+    Ok(Some(
+        validator.feed(default.as_bytes()) == FeedResult::Accepting,
+    ))
+}
+
+/// Default nesting depth of unconstrained objects and arrays.
+pub const DEFAULT_UNCONSTRAINED_DEPTH: usize = 2;
+
+/// Options of regular expression generation from JSON schema.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GenerationOptions {
+    /// Whitespace pattern, if `None`, the default [`WHITESPACE`] pattern is used.
+    pub whitespace_pattern: Option<String>,
+    /// Maximum recursion depth, if `None`, the default level of 3 is used.
+    pub max_recursion_depth: Option<usize>,
+    /// Order of object properties, [`PropertyOrder::Strict`] by default.
+    pub property_order: PropertyOrder,
+    /// Nesting depth of objects and arrays within unconstrained objects and arrays,
+    /// [`DEFAULT_UNCONSTRAINED_DEPTH`] by default.
+    pub unconstrained_depth: usize,
+    /// Pattern of integers without digit bounds, replacing the default [`INTEGER`].
+    pub integer_pattern: Option<String>,
+    /// Pattern of numbers without digit bounds, replacing the default [`NUMBER`].
+    pub number_pattern: Option<String>,
+    /// Allows control characters, like tabs or newlines, to appear literally within strings.
+    pub allow_control_chars_in_strings: bool,
+    /// Whitespace pattern right inside of brackets of arrays, tuples and objects without
+    /// `properties`, if `None`, the whitespace pattern is used.
+    pub bracket_whitespace: Option<String>,
+    /// Whitespace pattern around commas between items of arrays, tuples and objects without
+    /// `properties`, if `None`, the whitespace pattern is used.
+    pub separator_whitespace: Option<String>,
+    /// Enforces exclusivity of `oneOf` branches by type, where possible: if there are both
+    /// plain `integer` and `number` branches, the latter matches only numbers with a non-zero
+    /// fractional part. Other branches are alternated as they are.
+    ///
+    /// Such `number` branch doesn't match exponent forms, like `1.5e-3`, since whether they're
+    /// integers depends on the exponent. It's not applied along with [`Self::number_pattern`],
+    /// which is used for the branch as it is.
+    pub strict_one_of: bool,
+    /// Emits non-capturing groups `(?:...)` instead of capturing ones, including groups
+    /// of user patterns. Captures are never used, so it only makes compiled automata smaller.
+    pub non_capturing_groups: bool,
+    /// Orders `enum` alternatives by serialized values, rather than by their declaration,
+    /// so reordered enums produce the same regex.
+    pub sort_enum: bool,
+}
+
+impl Default for GenerationOptions {
+    fn default() -> Self {
+        Self {
+            whitespace_pattern: None,
+            max_recursion_depth: None,
+            property_order: PropertyOrder::default(),
+            unconstrained_depth: DEFAULT_UNCONSTRAINED_DEPTH,
+            integer_pattern: None,
+            number_pattern: None,
+            allow_control_chars_in_strings: false,
+            bracket_whitespace: None,
+            separator_whitespace: None,
+            strict_one_of: false,
+            non_capturing_groups: false,
+            sort_enum: false,
+        }
+    }
+}
+
+/// Order in which properties of an object are allowed to appear.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PropertyOrder {
+    /// Properties appear in the order of their declaration in the schema.
+    #[default]
+    Strict,
+    /// Properties appear in any order. Since every permutation is enumerated, it's supported
+    /// for objects with up to 6 properties.
+    AnyOrder,
+}
+
+/// Generates a regular expression string from `serde_json::Value` type of JSON schema
+/// with given [`GenerationOptions`].
+pub fn regex_from_value_with_options(json: &Value, options: &GenerationOptions) -> Result<String> {
+    for pattern in [
+        &options.integer_pattern,
+        &options.number_pattern,
+        &options.bracket_whitespace,
+        &options.separator_whitespace,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        parsing::Parser::validate_pattern(pattern)?;
+    }
+    let regex = parser(json, options).to_regex(json)?;
+    if options.non_capturing_groups {
+        Ok(parsing::non_capturing_groups(&regex))
+    } else {
+        Ok(regex)
+    }
+}
+
+/// Generates a regular expression string from `serde_json::Value` type of JSON schema
+/// with given [`GenerationOptions`], pointing to the failing part of the schema on error.
+///
+/// Errors are wrapped into [`Error::JsonSchemaAt`] with JSON pointer to the innermost
+/// sub-schema, which fails regex generation. Unsupported sub-schema is reported with its
+/// keywords as [`Error::UnsupportedKeywords`], rather than with the whole sub-schema.
+///
+/// # Example
+///
+/// ```rust
+/// use serde_json::json;
+/// use outlines_core::Error;
+/// use outlines_core::json_schema::{regex_from_value_verbose, GenerationOptions};
+///
+/// let schema = json!({"type": "object", "properties": {"a": {"type": "set"}}});
+/// match regex_from_value_verbose(&schema, &GenerationOptions::default()) {
+///     Err(Error::JsonSchemaAt { pointer, .. }) => assert_eq!(&*pointer, "/properties/a"),
+///     _ => unreachable!(),
+/// }
+/// ```
+pub fn regex_from_value_verbose(json: &Value, options: &GenerationOptions) -> Result<String> {
+    regex_from_value_with_options(json, options).map_err(|e| Error::JsonSchemaAt {
+        pointer: Box::from(diagnostics::locate_error(json, options)),
+        source: Box::new(diagnostics::localize_error(e)),
+    })
+}
+
+fn parser<'a>(root: &'a Value, options: &'a GenerationOptions) -> parsing::Parser<'a> {
+    let mut parser = parsing::Parser::new(root);
+    if let Some(pattern) = &options.whitespace_pattern {
         parser = parser.with_whitespace_pattern(pattern)
     }
-    if let Some(depth) = max_recursion_depth {
+    if let Some(depth) = options.max_recursion_depth {
         parser = parser.with_max_recursion_depth(depth)
     }
-    parser.to_regex(json)
+    if let Some(pattern) = &options.integer_pattern {
+        parser = parser.with_integer_pattern(pattern)
+    }
+    if let Some(pattern) = &options.number_pattern {
+        parser = parser.with_number_pattern(pattern)
+    }
+    if let Some(pattern) = &options.bracket_whitespace {
+        parser = parser.with_bracket_whitespace(pattern)
+    }
+    if let Some(pattern) = &options.separator_whitespace {
+        parser = parser.with_separator_whitespace(pattern)
+    }
+    parser
+        .with_property_order(options.property_order)
+        .with_unconstrained_depth(options.unconstrained_depth)
+        .with_control_chars_in_strings(options.allow_control_chars_in_strings)
+        .with_strict_one_of(options.strict_one_of)
+        .with_sort_enum(options.sort_enum)
+}
+
+/// Builds an [`Index`] from given JSON schema string and [`Vocabulary`] in one call.
+///
+/// It's a shortcut for [`Index::new_from_schema`], exported by the prelude.
+pub fn build_index_from_schema(
+    schema: &str,
+    vocabulary: &Vocabulary,
+    options: &GenerationOptions,
+) -> Result<Index> {
+    Index::new_from_schema(schema, vocabulary, options)
 }
 
 #[cfg(test)]
 mod tests {
     use regex::Regex;
 
+    use serde_json::json;
+
     use super::*;
+    use crate::Error;
 
     fn should_match(re: &Regex, value: &str) {
         // Asserts that value is fully matched.
@@ -312,6 +621,20 @@ mod tests {
                 vec!["1", "0", "1.3", "-1.3", "1.3e+9"],
                 vec!["01", ".3", "1.3e9"],
             ),
+            // Float alias of number type
+            (
+                r#"{"title": "Foo", "type": "float"}"#,
+                NUMBER,
+                vec!["1", "1.3", "-1.3", "1.3e+9"],
+                vec!["01", ".3", "a"],
+            ),
+            // Decimal alias of number type
+            (
+                r#"{"title": "Foo", "type": "decimal"}"#,
+                NUMBER,
+                vec!["1", "1.3", "-1.3"],
+                vec!["01", "1,3"],
+            ),
             // Required number property
             (
                 r#"{
@@ -517,9 +840,33 @@ mod tests {
             // String defined by a regular expression
             (
                 r#"{"title": "Foo", "type": "string", "pattern": "^[a-z]$"}"#,
-                r#"("[a-z]")"#,
+                r#"("(?:[a-z])")"#,
                 vec![r#""a""#], vec![r#""1""#],
             ),
+            // String pattern combined with length bounds
+            (
+                r#"{"title": "Foo", "type": "string", "pattern": "^[a-z]+$", "maxLength": 5}"#,
+                r#"("(?:[a-z]{1,5})")"#,
+                vec![r#""abc""#, r#""abcde""#],
+                vec![r#""abcdef""#, r#""ab1""#, r#""""#],
+            ),
+            (
+                r#"{"title": "Foo", "type": "string", "pattern": "\\d*", "minLength": 2, "maxLength": 3}"#,
+                r#"("(?:\d{2,3})")"#,
+                vec![r#""12""#, r#""123""#], vec![r#""1""#, r#""1234""#, r#""ab""#],
+            ),
+            // String pattern with alternation between anchors
+            (
+                r#"{"title": "Foo", "type": "string", "pattern": "^a|b$"}"#,
+                r#"("(?:a|b)")"#,
+                vec![r#""a""#, r#""b""#], vec![r#""ab""#, r#"a"#, r#""a"b"#],
+            ),
+            // String pattern with a single anchor and an escaped dollar
+            (
+                r#"{"title": "Foo", "type": "string", "pattern": "^[0-9]+\\$"}"#,
+                r#"("(?:[0-9]+\$)")"#,
+                vec![r#""12$""#], vec![r#""12""#],
+            ),
             // Make sure strings are escaped with regex escaping
             (
                 r#"{"title": "Foo", "const": ".*", "type": "string"}"#,
@@ -565,6 +912,19 @@ mod tests {
                 "null",
                 vec!["null"], vec!["none", ""],
             ),
+            // Const array
+            (
+                r#"{"title": "Foo", "const": [1, 2], "type": "array"}"#,
+                format!(r#"\[{0}1{0},{0}2{0}\]"#, WHITESPACE).as_str(),
+                vec!["[1,2]", "[1, 2]", "[ 1,2 ]"], vec!["[1]", "[2,1]", "[1,2,3]", "[1,  2]"],
+            ),
+            // Const object
+            (
+                r#"{"title": "Foo", "const": {"a": "b", "c.d": [true]}, "type": "object"}"#,
+                format!(r#"\{{{0}"a"{0}:{0}"b"{0},{0}"c\.d"{0}:{0}\[{0}true{0}\]{0}\}}"#, WHITESPACE).as_str(),
+                vec![r#"{"a":"b","c.d":[true]}"#, r#"{ "a": "b", "c.d": [ true ] }"#],
+                vec![r#"{"a":"b"}"#, r#"{"a":"c","c.d":[true]}"#, r#"{"a":"b","cxd":[true]}"#],
+            ),
             // ==========================================================
             //                      Enum
             // ==========================================================
@@ -1112,6 +1472,48 @@ mod tests {
                     r#""username@.example..com""#,         // multiple errors in domain
                 ]
             ),
+            // ==========================================================
+            //                  Byte and Binary Formats
+            // ==========================================================
+            (
+                r#"{"title": "Foo", "type": "string", "format": "byte"}"#,
+                BYTE,
+                vec![
+                    r#""""#,
+                    r#""aGVsbG8=""#,
+                    r#""aGVsbG8hIQ==""#,
+                    r#""U29tZSsvZGF0YQ==""#,
+                    r#""YWJj""#,
+                ],
+                vec![
+                    r#""aGVsbG8""#,    // missing padding
+                    r#""aGVs*G8=""#,   // not base64 character
+                    r#""aGVsbG8=YQ""#, // padding in the middle
+                    r#""a===""#,       // too much padding
+                    r#"aGVsbG8="#,     // missing quotes
+                ]
+            ),
+            (
+                r#"{"title": "Foo", "type": "string", "format": "binary"}"#,
+                BINARY,
+                vec![r#""""#, r#""any bytes \" and \\""#],
+                vec![r#""unterminated"#, r#"1"#]
+            ),
+            // ==========================================================
+            //                      Color Format
+            // ==========================================================
+            (
+                r#"{"title": "Foo", "type": "string", "format": "color"}"#,
+                COLOR,
+                vec![r##""#fff""##, r##""#aabbcc""##, r##""#aabbccdd""##, r##""#A0B1C2""##],
+                vec![
+                    r#""red""#,          // named colors aren't supported
+                    r##""#12""##,        // too short
+                    r##""#12345""##,     // neither 3, 6 nor 8 digits
+                    r##""#ggg""##,       // not hex digits
+                    r#""aabbcc""#,       // missing leading '#'
+                ]
+            ),
             // Nested URI and email
             (
                 r#"{
@@ -1244,6 +1646,14 @@ mod tests {
             let result = regex_from_str(schema, None, None).expect("To regex failed");
             assert_eq!(result, regex, "JSON Schema {} didn't match", schema);
 
+            // Regex is appended to the existing content of the buffer.
+            let json: Value = serde_json::from_str(schema).expect("Invalid JSON");
+            let mut buffer = String::from("prefix");
+            parser(&json, &GenerationOptions::default())
+                .to_regex_into(&json, &mut buffer)
+                .expect("To regex failed");
+            assert_eq!(buffer.strip_prefix("prefix"), Some(result.as_str()));
+
             let re = Regex::new(&result).expect("Regex failed");
             for m in a_match {
                 should_match(&re, m);
@@ -1308,6 +1718,799 @@ mod tests {
         }
     }
 
+    #[test]
+    fn annotations_are_ignored() {
+        let schema = r#"{
+            "type": "object",
+            "readOnly": true,
+            "$comment": "Annotated object",
+            "properties": {
+                "id": {"type": "integer", "readOnly": true, "examples": [1, 2]},
+                "secret": {"type": "string", "writeOnly": true, "deprecated": true, "default": "x"}
+            },
+            "required": ["id", "secret"]
+        }"#;
+        let regex = regex_from_str(schema, None, None).expect("To regex failed");
+        assert_eq!(
+            regex,
+            format!(r#"\{{[ ]?"id"[ ]?:[ ]?{INTEGER}[ ]?,[ ]?"secret"[ ]?:[ ]?{STRING}[ ]?\}}"#)
+        );
+
+        let unconstrained = regex_from_str("{}", None, None).expect("To regex failed");
+        for schema in [
+            r#"{"readOnly": true}"#,
+            r#"{"$comment": "anything", "deprecated": false}"#,
+        ] {
+            let regex = regex_from_str(schema, None, None).expect("To regex failed");
+            assert_eq!(regex, unconstrained);
+        }
+    }
+
+    #[test]
+    fn title_and_description_only_is_unconstrained() {
+        let unconstrained = regex_from_str("{}", None, None).expect("To regex failed");
+        for schema in [
+            r#"{"title": "x"}"#,
+            r#"{"description": "Anything goes"}"#,
+            r#"{"title": "x", "description": "Anything goes"}"#,
+        ] {
+            let regex = regex_from_str(schema, None, None).expect("To regex failed");
+            assert_eq!(regex, unconstrained);
+
+            let re = Regex::new(&regex).expect("Regex failed");
+            for m in ["1", r#""a""#, "null", r#"{"a": [1, 2]}"#] {
+                should_match(&re, m);
+            }
+        }
+    }
+
+    #[test]
+    fn build_index_from_schema_matches_manual_build() {
+        let schema =
+            r#"{"type": "object", "properties": {"a": {"type": "boolean"}}, "required": ["a"]}"#;
+        let mut vocabulary = Vocabulary::new(6);
+        for (token, token_id) in [
+            ("{", 0),
+            ("}", 1),
+            (r#""a""#, 2),
+            (":", 3),
+            ("true", 4),
+            (" ", 5),
+        ] {
+            vocabulary
+                .try_insert(token, token_id)
+                .expect("Insert failed");
+        }
+        let options = GenerationOptions {
+            whitespace_pattern: Some(" ?".to_string()),
+            ..GenerationOptions::default()
+        };
+
+        let index = build_index_from_schema(schema, &vocabulary, &options).expect("Index failed");
+        let regex = regex_from_str(schema, Some(" ?"), None).expect("To regex failed");
+        assert_eq!(
+            index,
+            Index::new(&regex, &vocabulary).expect("Index failed")
+        );
+
+        let mut state = index.initial_state();
+        for token_id in [0, 2, 3, 5, 4, 1] {
+            state = index.next_state(&state, &token_id).expect("Next state");
+        }
+        assert!(index.is_final_state(&state));
+
+        assert!(build_index_from_schema("{", &vocabulary, &options).is_err());
+    }
+
+    #[test]
+    fn parsing_errors_surface_as_crate_errors() {
+        let result: crate::Result<String> = regex_from_str("{", None, None);
+        assert!(matches!(result, Err(Error::SerdeJsonError(_))));
+
+        let result = regex_from_str(r#"{"type": "object", "allOf": 1}"#, None, None);
+        assert!(matches!(result, Err(Error::AllOfMustBeAnArray)));
+
+        let result = regex_from_str(r#"{"$ref": "other.json#/a"}"#, None, None);
+        assert!(matches!(
+            result,
+            Err(Error::ExternalReferencesNotSupported(_))
+        ));
+    }
+
+    #[test]
+    fn property_count_with_defined_properties() {
+        let schema = r#"{
+            "type": "object",
+            "properties": {
+                "a": {"type": "integer"},
+                "b": {"type": "integer"},
+                "c": {"type": "integer"}
+            },
+            "minProperties": 2
+        }"#;
+        let regex = regex_from_str(schema, None, None).expect("To regex failed");
+        let re = Regex::new(&regex).expect("Regex failed");
+        for m in [
+            r#"{"a": 1, "b": 2}"#,
+            r#"{"a": 1, "c": 3}"#,
+            r#"{"b": 2, "c": 3}"#,
+            r#"{"a": 1, "b": 2, "c": 3}"#,
+        ] {
+            should_match(&re, m);
+        }
+        for not_m in ["{}", r#"{"a": 1}"#, r#"{"c": 3}"#, r#"{"b": 2, "a": 1}"#] {
+            should_not_match(&re, not_m);
+        }
+
+        let schema = r#"{
+            "type": "object",
+            "properties": {
+                "a": {"type": "integer"},
+                "b": {"type": "integer"},
+                "c": {"type": "integer"}
+            },
+            "required": ["b"],
+            "maxProperties": 2
+        }"#;
+        let regex = regex_from_str(schema, None, None).expect("To regex failed");
+        let re = Regex::new(&regex).expect("Regex failed");
+        for m in [r#"{"b": 2}"#, r#"{"a": 1, "b": 2}"#, r#"{"b": 2, "c": 3}"#] {
+            should_match(&re, m);
+        }
+        for not_m in ["{}", r#"{"a": 1, "b": 2, "c": 3}"#] {
+            should_not_match(&re, not_m);
+        }
+
+        let schema = r#"{
+            "type": "object",
+            "properties": {"a": {"type": "integer"}},
+            "minProperties": 2
+        }"#;
+        let result = regex_from_str(schema, None, None);
+        assert!(matches!(result, Err(Error::UnsatisfiablePropertyCount)));
+
+        let properties: serde_json::Map<String, Value> = (0..11)
+            .map(|i| (format!("p{i}"), serde_json::json!({"type": "null"})))
+            .collect();
+        let schema =
+            serde_json::json!({"type": "object", "properties": properties, "maxProperties": 3});
+        let result = regex_from_value(&schema, None, None);
+        assert!(matches!(
+            result,
+            Err(Error::TooManyOptionalPropertiesForCount(11, 10))
+        ));
+    }
+
+    #[test]
+    fn check_examples_reports_matches() {
+        let schema = r#"{
+            "type": "object",
+            "properties": {"name": {"type": "string"}},
+            "required": ["name"],
+            "examples": [{"name": "John"}, {"name": 42}],
+            "default": {"name": ""}
+        }"#;
+        let checked = check_examples(schema).expect("Check failed");
+        assert_eq!(
+            checked,
+            vec![
+                (r#"{"name":"John"}"#.to_string(), true),
+                (r#"{"name":42}"#.to_string(), false),
+                (r#"{"name":""}"#.to_string(), true),
+            ]
+        );
+
+        let checked = check_examples(r#"{"type": "string"}"#).expect("Check failed");
+        assert!(checked.is_empty());
+
+        let schema = r#"{"type": "number", "examples": [1.5, 10, "1.5"]}"#;
+        let checked = check_examples(schema).expect("Check failed");
+        assert_eq!(
+            checked,
+            vec![
+                ("1.5".to_string(), true),
+                ("10".to_string(), true),
+                (r#""1.5""#.to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn property_order() {
+        let schema: Value = serde_json::from_str(
+            r#"{
+                "type": "object",
+                "properties": {"name": {"type": "string"}, "age": {"type": "integer"}},
+                "required": ["name", "age"]
+            }"#,
+        )
+        .expect("Schema failed");
+        let reordered = r#"{"age":1,"name":"x"}"#;
+
+        let strict = regex_from_value_with_options(&schema, &GenerationOptions::default())
+            .expect("To regex failed");
+        let re = Regex::new(&strict).expect("Regex failed");
+        should_match(&re, r#"{"name":"x","age":1}"#);
+        should_not_match(&re, reordered);
+
+        let options = GenerationOptions {
+            property_order: PropertyOrder::AnyOrder,
+            ..GenerationOptions::default()
+        };
+        let any_order = regex_from_value_with_options(&schema, &options).expect("To regex failed");
+        let re = Regex::new(&any_order).expect("Regex failed");
+        should_match(&re, r#"{"name":"x","age":1}"#);
+        should_match(&re, reordered);
+        should_not_match(&re, r#"{"age":1}"#);
+
+        let properties: serde_json::Map<String, Value> = (0..7)
+            .map(|i| (format!("p{i}"), serde_json::json!({"type": "null"})))
+            .collect();
+        let schema = serde_json::json!({"type": "object", "properties": properties});
+        let result = regex_from_value_with_options(&schema, &options);
+        assert!(matches!(
+            result,
+            Err(Error::TooManyPropertiesForAnyOrder(7, 6))
+        ));
+    }
+
+    #[test]
+    fn additional_properties_with_defined_properties() {
+        let closed = r#"{
+            "type": "object",
+            "properties": {"a": {"type": "integer"}, "b": {"type": "integer"}},
+            "required": ["a"],
+            "additionalProperties": false
+        }"#;
+        let regex = regex_from_str(closed, None, None).expect("To regex failed");
+        let re = Regex::new(&regex).expect("Regex failed");
+        for m in [r#"{"a": 1}"#, r#"{"a": 1, "b": 2}"#] {
+            should_match(&re, m);
+        }
+        for not_m in [r#"{"a": 1, "c": 3}"#, r#"{"a": 1, "b": 2, "c": 3}"#] {
+            should_not_match(&re, not_m);
+        }
+
+        let open = r#"{
+            "type": "object",
+            "properties": {"a": {"type": "integer"}, "b": {"type": "integer"}},
+            "required": ["a"],
+            "additionalProperties": true
+        }"#;
+        let regex = regex_from_str(open, None, None).expect("To regex failed");
+        let re = Regex::new(&regex).expect("Regex failed");
+        for m in [
+            r#"{"a": 1}"#,
+            r#"{"a": 1, "c": "x"}"#,
+            r#"{"a": 1, "b": 2, "c": [1], "d": null}"#,
+        ] {
+            should_match(&re, m);
+        }
+        for not_m in [r#"{"c": 3}"#, r#"{"a": 1,}"#] {
+            should_not_match(&re, not_m);
+        }
+
+        let typed = r#"{
+            "type": "object",
+            "properties": {"a": {"type": "integer"}},
+            "additionalProperties": {"type": "boolean"}
+        }"#;
+        let regex = regex_from_str(typed, None, None).expect("To regex failed");
+        let re = Regex::new(&regex).expect("Regex failed");
+        for m in [
+            "{}",
+            r#"{"a": 1}"#,
+            r#"{"c": true}"#,
+            r#"{"a": 1, "c": false}"#,
+        ] {
+            should_match(&re, m);
+        }
+        for not_m in [r#"{"c": 1}"#, r#"{, "c": true}"#, r#"{"a": 1, "c": 1}"#] {
+            should_not_match(&re, not_m);
+        }
+    }
+
+    #[test]
+    fn unconstrained_depth() {
+        let schema: Value = serde_json::from_str(r#"{"type": "array"}"#).expect("Schema failed");
+        let regex_with_depth = |unconstrained_depth| {
+            let options = GenerationOptions {
+                unconstrained_depth,
+                ..GenerationOptions::default()
+            };
+            let regex = regex_from_value_with_options(&schema, &options).expect("To regex failed");
+            Regex::new(&regex).expect("Regex failed")
+        };
+
+        let shallow = regex_with_depth(1);
+        should_match(&shallow, "[[1], {}]");
+        should_not_match(&shallow, "[[[1]]]");
+
+        let deep = regex_with_depth(3);
+        should_match(&deep, "[[[1]]]");
+        should_match(&deep, r#"[[{"a": [1]}]]"#);
+        should_not_match(&deep, "[[[[[1]]]]]");
+
+        let default = regex_from_str(r#"{"type": "array"}"#, None, None).expect("To regex failed");
+        assert_eq!(
+            default,
+            regex_with_depth(DEFAULT_UNCONSTRAINED_DEPTH).as_str()
+        );
+    }
+
+    #[test]
+    fn verbose_errors_point_to_failing_sub_schema() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "ok": {"type": "string"},
+                "a/b": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {"c": {"type": "string", "format": "unknown"}}
+                    }
+                },
+                "d": {"$ref": "#/$defs/D"}
+            },
+            "$defs": {"D": {"anyOf": [{"type": "null"}, {"type": "set"}]}}
+        });
+        let options = GenerationOptions::default();
+        match regex_from_value_verbose(&schema, &options) {
+            Err(Error::JsonSchemaAt { pointer, source }) => {
+                assert_eq!(&*pointer, "/properties/a~1b/items/properties/c");
+                assert!(matches!(*source, Error::StringTypeUnsupportedFormat(_)));
+            }
+            other => panic!("Expected JsonSchemaAt error, got {:?}", other),
+        }
+
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {"d": {"$ref": "#/$defs/D"}},
+            "$defs": {"D": {"anyOf": [{"type": "null"}, {"type": "set"}]}}
+        });
+        match regex_from_value_verbose(&schema, &options) {
+            Err(Error::JsonSchemaAt { pointer, source }) => {
+                assert_eq!(&*pointer, "/$defs/D/anyOf/1");
+                assert!(matches!(*source, Error::UnsupportedType(_)));
+            }
+            other => panic!("Expected JsonSchemaAt error, got {:?}", other),
+        }
+
+        let schema = serde_json::json!({"type": "set"});
+        match regex_from_value_verbose(&schema, &options) {
+            Err(Error::JsonSchemaAt { pointer, .. }) => assert_eq!(&*pointer, ""),
+            other => panic!("Expected JsonSchemaAt error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unsupported_type_alias_is_reported() {
+        let result = regex_from_str(r#"{"type": "bigint"}"#, None, None);
+        match result {
+            Err(e @ Error::UnsupportedTypeAlias { .. }) => {
+                assert_eq!(
+                    e.to_string(),
+                    r#"Unsupported type: bigint, use "integer" instead"#
+                )
+            }
+            other => panic!("Expected UnsupportedTypeAlias error, got {:?}", other),
+        }
+
+        let result = regex_from_str(r#"{"type": "set"}"#, None, None);
+        assert!(matches!(result, Err(Error::UnsupportedType(_))));
+    }
+
+    #[test]
+    fn custom_integer_and_number_patterns() {
+        let schema: Value = serde_json::from_str(
+            r#"{
+                "type": "object",
+                "properties": {"id": {"type": "integer"}, "score": {"type": "number"}},
+                "required": ["id", "score"]
+            }"#,
+        )
+        .expect("Schema failed");
+        let options = GenerationOptions {
+            integer_pattern: Some("[0-9]+".to_string()),
+            number_pattern: Some(r"[+-][0-9]+\.[0-9]+".to_string()),
+            ..GenerationOptions::default()
+        };
+        let regex = regex_from_value_with_options(&schema, &options).expect("To regex failed");
+        let re = Regex::new(&regex).expect("Regex failed");
+        should_match(&re, r#"{"id": 007, "score": +1.5}"#);
+        should_not_match(&re, r#"{"id": -7, "score": +1.5}"#);
+        should_not_match(&re, r#"{"id": 7, "score": 1.5}"#);
+
+        let default = regex_from_value(&schema, None, None).expect("To regex failed");
+        let re = Regex::new(&default).expect("Regex failed");
+        should_not_match(&re, r#"{"id": 007, "score": 1.5}"#);
+
+        let options = GenerationOptions {
+            integer_pattern: Some("[0-9+".to_string()),
+            ..GenerationOptions::default()
+        };
+        let result = regex_from_value_with_options(&schema, &options);
+        assert!(matches!(result, Err(Error::InvalidPattern { .. })));
+    }
+
+    #[test]
+    fn control_chars_in_strings() {
+        let schema: Value = serde_json::from_str(
+            r#"{
+                "type": "object",
+                "properties": {
+                    "text": {"type": "string"},
+                    "short": {"type": "string", "maxLength": 3}
+                },
+                "required": ["text", "short"]
+            }"#,
+        )
+        .expect("Schema failed");
+        let with_tab = "{\"text\": \"a\tb\", \"short\": \"\t\n\"}";
+        let escaped = r#"{"text": "a\tb", "short": "\n"}"#;
+
+        let regex = regex_from_value_with_options(&schema, &GenerationOptions::default())
+            .expect("To regex failed");
+        let re = Regex::new(&regex).expect("Regex failed");
+        should_match(&re, escaped);
+        should_not_match(&re, with_tab);
+
+        let options = GenerationOptions {
+            allow_control_chars_in_strings: true,
+            ..GenerationOptions::default()
+        };
+        let regex = regex_from_value_with_options(&schema, &options).expect("To regex failed");
+        let re = Regex::new(&regex).expect("Regex failed");
+        should_match(&re, escaped);
+        should_match(&re, with_tab);
+        should_not_match(&re, "{\"text\": \"a\"b\", \"short\": \"\"}");
+    }
+
+    #[test]
+    fn ref_to_array_elements() {
+        let schema = r##"{
+            "type": "array",
+            "prefixItems": [
+                {"type": "integer"},
+                {"$ref": "#/prefixItems/0"},
+                {"$ref": "#/$defs/choices/1"}
+            ],
+            "$defs": {"choices": [{"type": "string"}, {"type": "boolean"}]}
+        }"##;
+        let regex = regex_from_str(schema, None, None).expect("To regex failed");
+        let re = Regex::new(&regex).expect("Regex failed");
+        should_match(&re, "[1, 2, true]");
+        should_not_match(&re, r#"[1, "a", true]"#);
+        should_not_match(&re, r#"[1, 2, "a"]"#);
+
+        for reference in ["#/prefixItems/3", "#/prefixItems/first"] {
+            let schema = serde_json::json!({
+                "type": "array",
+                "prefixItems": [{"type": "integer"}, {"$ref": reference}]
+            });
+            let result = regex_from_value(&schema, None, None);
+            assert!(matches!(result, Err(Error::InvalidRefecencePath(_))));
+        }
+    }
+
+    /// Minimal linear congruential generator, enough to produce reproducible random schemas.
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((self.0 >> 33) as usize) % bound
+        }
+
+        fn value(&mut self, depth: usize) -> Value {
+            const KEYS: [&str; 24] = [
+                "type",
+                "properties",
+                "required",
+                "items",
+                "prefixItems",
+                "allOf",
+                "anyOf",
+                "oneOf",
+                "enum",
+                "const",
+                "$ref",
+                "$defs",
+                "$id",
+                "$anchor",
+                "minLength",
+                "maxLength",
+                "pattern",
+                "format",
+                "minItems",
+                "maxItems",
+                "minProperties",
+                "maxProperties",
+                "additionalProperties",
+                "a",
+            ];
+            const STRINGS: [&str; 14] = [
+                "string",
+                "integer",
+                "number",
+                "boolean",
+                "null",
+                "array",
+                "object",
+                "#",
+                "#/$defs/a",
+                "#/properties/a",
+                "#a",
+                "date-time",
+                "[a-z]+",
+                "",
+            ];
+            let leaf = depth == 0;
+            match self.next(if leaf { 5 } else { 7 }) {
+                0 => Value::Null,
+                1 => Value::Bool(self.next(2) == 0),
+                2 => json!([-1, 0, 1, 3, 1000][self.next(5)]),
+                3 => json!([u64::MAX, 1][self.next(2)]),
+                4 => Value::String(STRINGS[self.next(STRINGS.len())].to_string()),
+                5 => Value::Array((0..self.next(4)).map(|_| self.value(depth - 1)).collect()),
+                _ => Value::Object(
+                    (0..self.next(5))
+                        .map(|_| {
+                            (
+                                KEYS[self.next(KEYS.len())].to_string(),
+                                self.value(depth - 1),
+                            )
+                        })
+                        .collect(),
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn random_schemas_never_panic() {
+        let mut rng = Lcg(42);
+        for _ in 0..5000 {
+            let schema = rng.value(4);
+            let _ = regex_from_value(&schema, None, Some(3));
+        }
+
+        let mut deep = json!({"type": "string"});
+        for _ in 0..200 {
+            deep = json!({"type": "object", "properties": {"a": deep}, "required": ["a"]});
+        }
+        assert!(regex_from_value(&deep, None, None).is_ok());
+    }
+
+    #[test]
+    fn strict_one_of_by_type() {
+        let strict = GenerationOptions {
+            strict_one_of: true,
+            ..GenerationOptions::default()
+        };
+
+        // Naturally disjoint branches are kept as they are.
+        let schema = json!({"oneOf": [{"type": "string"}, {"type": "integer"}]});
+        let regex = regex_from_value_with_options(&schema, &strict).expect("To regex failed");
+        assert_eq!(
+            regex,
+            regex_from_value(&schema, None, None).expect("To regex failed")
+        );
+        assert_eq!(regex, format!("((?:{STRING})|(?:{INTEGER}))"));
+
+        let schema = json!({"oneOf": [{"type": "integer"}, {"type": "number"}]});
+        let regex = regex_from_value_with_options(&schema, &strict).expect("To regex failed");
+        assert_eq!(regex, format!("((?:{INTEGER})|(?:{NON_INTEGER_NUMBER}))"));
+        let re = Regex::new(&format!("^{regex}$")).expect("Regex failed");
+        for m in ["1", "-12", "1.5", "0.05"] {
+            assert!(re.is_match(m), "{m}");
+        }
+        let number = Regex::new(&format!("^{NON_INTEGER_NUMBER}$")).expect("Regex failed");
+        for not_m in ["1", "1.0", "-0.00", "1.5e-3"] {
+            assert!(!number.is_match(not_m), "{not_m}");
+        }
+
+        // Custom number pattern takes precedence.
+        let options = GenerationOptions {
+            number_pattern: Some(r"[0-9]+\.[0-9]+".to_string()),
+            ..strict.clone()
+        };
+        let regex = regex_from_value_with_options(&schema, &options).expect("To regex failed");
+        assert_eq!(regex, format!(r"((?:{INTEGER})|(?:(?:[0-9]+\.[0-9]+)))"));
+
+        // Overlapping branches still compile, as a plain alternation.
+        let schema = json!({"oneOf": [{"type": "string"}, {"type": "string", "maxLength": 3}]});
+        for options in [&strict, &GenerationOptions::default()] {
+            let regex = regex_from_value_with_options(&schema, options).expect("To regex failed");
+            let re = Regex::new(&regex).expect("Regex failed");
+            assert!(re.is_match(r#""ab""#));
+        }
+    }
+
+    #[test]
+    fn bracket_and_separator_whitespace() {
+        let schema = json!({"type": "array", "items": {"type": "integer"}});
+        let options = GenerationOptions {
+            bracket_whitespace: Some(String::new()),
+            separator_whitespace: Some(String::from(" ")),
+            ..GenerationOptions::default()
+        };
+        let regex = regex_from_value_with_options(&schema, &options).expect("To regex failed");
+        let re = Regex::new(&format!("^{regex}$")).expect("Regex failed");
+        for m in ["[]", "[1]", "[1, 2, 3]"] {
+            assert!(re.is_match(m), "{m}");
+        }
+        for not_m in ["[ 1, 2]", "[1,2]", "[1, 2 ]", "[ ]"] {
+            assert!(!re.is_match(not_m), "{not_m}");
+        }
+
+        let schema = json!({"type": "object"});
+        let regex = regex_from_value_with_options(&schema, &options).expect("To regex failed");
+        let re = Regex::new(&format!("^{regex}$")).expect("Regex failed");
+        assert!(re.is_match(r#"{"a":1 , "b":2}"#));
+        assert!(!re.is_match(r#"{ "a":1 , "b":2}"#));
+        assert!(!re.is_match(r#"{"a":1,"b":2}"#));
+    }
+
+    #[test]
+    fn default_value_matches() {
+        let schema =
+            r#"{"type": "object", "properties": {"n": {"type": "integer"}}, "default": {"n": 1}}"#;
+        assert_eq!(default_matches(schema).expect("Check failed"), Some(true));
+
+        let schema = r#"{"type": "integer", "minimum": 0, "default": "zero"}"#;
+        assert_eq!(default_matches(schema).expect("Check failed"), Some(false));
+
+        let schema = r#"{"type": "integer"}"#;
+        assert_eq!(default_matches(schema).expect("Check failed"), None);
+
+        let schema = r#"{"type": "number", "default": 1.5}"#;
+        assert_eq!(default_matches(schema).expect("Check failed"), Some(true));
+    }
+
+    #[test]
+    fn invalid_length_bounds() {
+        for (schema, key, value) in [
+            (
+                r#"{"type": "string", "maxLength": -1}"#,
+                "maxLength",
+                json!(-1),
+            ),
+            (
+                r#"{"type": "string", "maxLength": "3"}"#,
+                "maxLength",
+                json!("3"),
+            ),
+            (
+                r#"{"type": "string", "minLength": 1.5}"#,
+                "minLength",
+                json!(1.5),
+            ),
+        ] {
+            match regex_from_str(schema, None, None) {
+                Err(Error::InvalidBound {
+                    key: error_key,
+                    value: error_value,
+                }) => {
+                    assert_eq!(error_key, key);
+                    assert_eq!(*error_value, value);
+                }
+                result => panic!("Expected InvalidBound for {schema}, got {result:?}"),
+            }
+        }
+
+        let schema = r#"{"type": "string", "minLength": 4, "maxLength": 3}"#;
+        let result = regex_from_str(schema, None, None);
+        assert!(matches!(result, Err(Error::MaxBoundError)));
+
+        let schema = r#"{"type": "string", "maxLength": 0, "pattern": "^[a-z]+$"}"#;
+        let result = regex_from_str(schema, None, None);
+        assert!(matches!(result, Err(Error::UnsatisfiableLength(1))));
+
+        for schema in [
+            r#"{"type": "string", "pattern": "^(ab)+$", "maxLength": 2}"#,
+            r#"{"type": "string", "pattern": "^(ab|c)$", "minLength": 2}"#,
+        ] {
+            let result = regex_from_str(schema, None, None);
+            assert!(
+                matches!(result, Err(Error::UnsupportedPatternWithLength(_))),
+                "Expected UnsupportedPatternWithLength for {schema}, got {result:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn enum_members_must_match_type() {
+        let schema = r#"{"type": "integer", "enum": [1, "two", 3]}"#;
+        let result = regex_from_str(schema, None, None);
+        match result {
+            Err(Error::EnumTypeMismatch {
+                value,
+                instance_type,
+            }) => {
+                assert_eq!(*value, json!("two"));
+                assert_eq!(&*instance_type, "integer");
+            }
+            _ => panic!("Expected EnumTypeMismatch, got {result:?}"),
+        }
+
+        let schema = r#"{"type": ["integer", "string"], "enum": [1, 2.0, "two"]}"#;
+        assert!(regex_from_str(schema, None, None).is_ok());
+
+        let schema = r#"{"type": "string", "enum": ["x"]}"#;
+        let regex = regex_from_str(schema, None, None).expect("To regex failed");
+        assert_eq!(regex, r#"("x")"#);
+    }
+
+    #[test]
+    fn ref_to_anchor() {
+        let schema = r##"{
+            "type": "object",
+            "properties": {
+                "home": {"$ref": "#addr"},
+                "work": {"$ref": "#/$defs/address"}
+            },
+            "required": ["home", "work"],
+            "$defs": {
+                "address": {
+                    "$anchor": "addr",
+                    "type": "object",
+                    "properties": {"city": {"type": "string"}},
+                    "required": ["city"]
+                }
+            }
+        }"##;
+        let regex = regex_from_str(schema, None, None).expect("To regex failed");
+        let re = Regex::new(&regex).expect("Regex failed");
+        should_match(&re, r#"{"home": {"city": "A"}, "work": {"city": "B"}}"#);
+        should_not_match(&re, r#"{"home": {"city": 1}, "work": {"city": "B"}}"#);
+
+        let schema = r##"{"type": "object", "properties": {"a": {"$ref": "#missing"}}}"##;
+        let result = regex_from_str(schema, None, None);
+        assert!(matches!(result, Err(Error::AnchorNotFound(_))));
+    }
+
+    #[test]
+    fn invalid_pattern_is_reported() {
+        let schema = r#"{"type": "string", "pattern": "^[a-z+$"}"#;
+        match regex_from_str(schema, None, None) {
+            Err(Error::InvalidPattern { pattern, .. }) => assert_eq!(&*pattern, "^[a-z+$"),
+            other => panic!("Expected InvalidPattern error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn format_on_non_string_is_reported() {
+        for (schema, instance_type) in [
+            (r#"{"type": "integer", "format": "int32"}"#, "integer"),
+            (
+                r#"{"type": ["number", "null"], "format": "date"}"#,
+                "number, null",
+            ),
+        ] {
+            match regex_from_str(schema, None, None) {
+                Err(Error::FormatOnNonString(found)) => assert_eq!(&*found, instance_type),
+                other => panic!("Expected FormatOnNonString error, got {:?}", other),
+            }
+        }
+
+        let schema = r#"{"type": ["string", "null"], "format": "date"}"#;
+        assert!(regex_from_str(schema, None, None).is_ok());
+
+        let schema = r#"{"type": "string", "format": "unknown"}"#;
+        match regex_from_str(schema, None, None) {
+            Err(Error::StringTypeUnsupportedFormat(format)) => assert_eq!(&*format, "unknown"),
+            other => panic!(
+                "Expected StringTypeUnsupportedFormat error, got {:?}",
+                other
+            ),
+        }
+    }
+
     #[test]
     fn with_whitespace_patterns() {
         let schema = r#"{
@@ -1626,4 +2829,462 @@ mod tests {
             "Regex should contain typeE when max_recursion_depth is specified"
         );
     }
+
+    #[test]
+    fn unsupported_keywords_reported() {
+        let schema = json!({
+            "title": "Tags",
+            "type": "object",
+            "properties": {
+                "tags": {"type": "array", "items": {"type": "string"}, "uniqueItems": true},
+                "name": {"not": {"const": "admin"}, "type": "string"},
+                "a/b": {"anyOf": [{"type": "integer", "multipleOf": 2}]}
+            },
+            "$defs": {"unused": {"uniqueItems": true}}
+        });
+        assert_eq!(
+            unsupported_keywords_in(&schema),
+            vec![
+                "/properties/tags/uniqueItems",
+                "/properties/name/not",
+                "/properties/a~1b/anyOf/0/multipleOf",
+                "/$defs/unused/uniqueItems",
+            ]
+        );
+
+        for keyword in ["type", "properties", "$ref", "title", "default"] {
+            assert!(supported_keywords().contains(&keyword));
+        }
+        for keyword in ["uniqueItems", "not", "multipleOf"] {
+            assert!(!supported_keywords().contains(&keyword));
+        }
+        assert!(unsupported_keywords_in(&json!({"type": "string", "format": "uuid"})).is_empty());
+    }
+
+    #[test]
+    fn boolean_schemas() {
+        let anything = regex_from_str("true", None, None).expect("To regex failed");
+        let unconstrained = regex_from_str("{}", None, None).expect("To regex failed");
+        assert_eq!(anything, format!("({unconstrained})"));
+
+        let nothing = regex_from_str("false", None, None).expect("To regex failed");
+        let re = Regex::new(&nothing).expect("Regex failed");
+        for value in ["", "null", "1", r#""a""#, "{}", "[]"] {
+            should_not_match(&re, value);
+        }
+
+        let schema = r#"{
+            "type": "object",
+            "properties": {"any": true, "none": false, "n": {"type": "integer"}},
+            "required": ["any"]
+        }"#;
+        let regex = regex_from_str(schema, None, None).expect("To regex failed");
+        let re = Regex::new(&regex).expect("Regex failed");
+        for value in [
+            r#"{"any":null}"#,
+            r#"{"any":[1,"x"],"n":2}"#,
+            r#"{"any":{"k":true}}"#,
+        ] {
+            should_match(&re, value);
+        }
+        for value in [
+            r#"{"n":2}"#,
+            r#"{"any":1,"none":1}"#,
+            r#"{"any":1,"none":null}"#,
+        ] {
+            should_not_match(&re, value);
+        }
+    }
+
+    #[test]
+    fn prefix_items_with_tail() {
+        let schema = r#"{
+            "type": "array",
+            "prefixItems": [{"type": "string"}, {"type": "boolean"}],
+            "items": {"type": "integer"},
+            "minItems": 3,
+            "maxItems": 4
+        }"#;
+        let regex = regex_from_str(schema, None, None).expect("To regex failed");
+        let re = Regex::new(&regex).expect("Regex failed");
+        for value in [r#"["a", true, 1]"#, r#"["a", false, 1, 2]"#] {
+            should_match(&re, value);
+        }
+        for value in [
+            r#"["a", true]"#,
+            r#"["a", true, 1, 2, 3]"#,
+            r#"["a", true, "b"]"#,
+            r#"[1, true, 1]"#,
+        ] {
+            should_not_match(&re, value);
+        }
+
+        // Unbounded tail.
+        let schema = r#"{"prefixItems": [{"type": "null"}], "items": {"type": "integer"}}"#;
+        let regex = regex_from_str(schema, None, None).expect("To regex failed");
+        let re = Regex::new(&regex).expect("Regex failed");
+        for value in ["[null]", "[null, 1]", "[null, 1, 2, 3]"] {
+            should_match(&re, value);
+        }
+        should_not_match(&re, "[null, null]");
+
+        // Empty prefix, so the first item has no separator.
+        let schema = r#"{"prefixItems": [], "items": {"type": "integer"}, "maxItems": 2}"#;
+        let regex = regex_from_str(schema, None, None).expect("To regex failed");
+        let re = Regex::new(&regex).expect("Regex failed");
+        for value in ["[]", "[1]", "[1, 2]"] {
+            should_match(&re, value);
+        }
+        should_not_match(&re, "[1, 2, 3]");
+
+        for schema in [
+            // Prefix is longer than maxItems.
+            r#"{"prefixItems": [{"type": "null"}, {"type": "null"}], "maxItems": 1}"#,
+            // No tail to reach minItems.
+            r#"{"prefixItems": [{"type": "null"}], "minItems": 2}"#,
+            r#"{"prefixItems": [{"type": "null"}], "items": false, "minItems": 2}"#,
+            r#"{"prefixItems": [], "items": {}, "minItems": 3, "maxItems": 2}"#,
+        ] {
+            assert!(
+                matches!(
+                    regex_from_str(schema, None, None),
+                    Err(Error::UnsatisfiableItemCount(_))
+                ),
+                "Expected UnsatisfiableItemCount for {schema}"
+            );
+        }
+    }
+
+    #[test]
+    fn verbose_errors_show_keywords_not_sub_schema() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "x": {"not": {"type": "object", "properties": {"deeply_nested_name": {}}}}
+            }
+        });
+        let error = regex_from_value_verbose(&schema, &GenerationOptions::default())
+            .expect_err("Schema is unsupported");
+        match &error {
+            Error::JsonSchemaAt { pointer, source } => {
+                assert_eq!(&**pointer, "/properties/x");
+                assert!(
+                    matches!(&**source, Error::UnsupportedKeywords(keywords) if keywords[..] == [Box::from("not")])
+                );
+            }
+            other => panic!("Expected JsonSchemaAt error, got {:?}", other),
+        }
+        let message = error.to_string();
+        assert!(message.contains("/properties/x"), "{message}");
+        assert!(message.contains("not"), "{message}");
+        assert!(!message.contains("deeply_nested_name"), "{message}");
+
+        // Without unsupported keywords, all of them are shown.
+        let schema = json!({"properties": {"x": {"minLength": 1}}});
+        match regex_from_value_verbose(&schema, &GenerationOptions::default()) {
+            Err(Error::JsonSchemaAt { source, .. }) => assert_eq!(
+                source.to_string(),
+                "Unsupported JSON Schema keywords: minLength"
+            ),
+            other => panic!("Expected JsonSchemaAt error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn property_names_with_min_properties() {
+        let schema = r#"{
+            "type": "object",
+            "propertyNames": {"pattern": "^[a-z]+$"},
+            "additionalProperties": {"type": "string"},
+            "minProperties": 1
+        }"#;
+        let regex = regex_from_str(schema, None, None).expect("To regex failed");
+        let re = Regex::new(&regex).expect("Regex failed");
+        for value in [r#"{"a": "x"}"#, r#"{"abc": "x", "de": "y"}"#] {
+            should_match(&re, value);
+        }
+        for value in [
+            "{}",
+            r#"{"1": "x"}"#,
+            r#"{"a1": "x"}"#,
+            r#"{"a": "x", "2": "y"}"#,
+            r#"{"a": 1}"#,
+        ] {
+            should_not_match(&re, value);
+        }
+    }
+
+    #[test]
+    fn property_names_with_property_count() {
+        let regex_of = |bounds: &str| {
+            let schema = format!(
+                r#"{{
+                    "type": "object",
+                    "propertyNames": {{"pattern": "^[a-z]+$"}},
+                    "additionalProperties": {{"type": "string"}}
+                    {bounds}
+                }}"#
+            );
+            let regex = regex_from_str(&schema, None, None).expect("To regex failed");
+            Regex::new(&regex).expect("Regex failed")
+        };
+        let one = r#"{"a": "x"}"#;
+        let two = r#"{"a": "x", "b": "y"}"#;
+        let three = r#"{"a": "x", "b": "y", "c": "z"}"#;
+
+        let re = regex_of(r#", "minProperties": 2"#);
+        should_match(&re, two);
+        should_match(&re, three);
+        should_not_match(&re, "{}");
+        should_not_match(&re, one);
+
+        let re = regex_of(r#", "minProperties": 2, "maxProperties": 2"#);
+        should_match(&re, two);
+        should_not_match(&re, one);
+        should_not_match(&re, three);
+
+        let re = regex_of(r#", "maxProperties": 2"#);
+        should_match(&re, "{}");
+        should_match(&re, one);
+        should_match(&re, two);
+        should_not_match(&re, three);
+
+        let schema = r#"{"type": "object", "minProperties": 3, "maxProperties": 2}"#;
+        assert!(matches!(
+            regex_from_str(schema, None, None),
+            Err(Error::UnsatisfiablePropertyCount)
+        ));
+    }
+
+    #[test]
+    fn non_capturing_groups_accept_same_strings() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string", "pattern": "^(a|b)[(]\\(x\\)$"},
+                "kind": {"enum": ["x", "y"]},
+                "tags": {"type": "array", "items": {"type": "integer"}, "maxItems": 3},
+                "extra": {}
+            },
+            "required": ["name"]
+        });
+        let capturing = regex_from_value_with_options(&schema, &GenerationOptions::default())
+            .expect("To regex failed");
+        let options = GenerationOptions {
+            non_capturing_groups: true,
+            ..GenerationOptions::default()
+        };
+        let non_capturing =
+            regex_from_value_with_options(&schema, &options).expect("To regex failed");
+
+        let capturing_re = Regex::new(&format!("^(?:{capturing})$")).expect("Regex failed");
+        let non_capturing_re = Regex::new(&format!("^(?:{non_capturing})$")).expect("Regex failed");
+        assert!(capturing_re.captures_len() > 1);
+        assert_eq!(non_capturing_re.captures_len(), 1);
+        for value in [
+            r#"{"name": "a(x)"}"#,
+            r#"{"name": "b(x)", "kind": "y", "tags": [1, 2]}"#,
+            r#"{"name": "a(x)", "extra": {"k": [null]}}"#,
+            r#"{"name": "c(x)"}"#,
+            r#"{"name": "a(x)", "tags": [1, 2, 3, 4]}"#,
+            r#"{"kind": "x"}"#,
+        ] {
+            assert_eq!(
+                capturing_re.is_match(value),
+                non_capturing_re.is_match(value),
+                "Modes disagree on {value}"
+            );
+        }
+
+        // Capture slots are the only difference, so compiled automata get smaller.
+        let nfa_size = |regex: &str| {
+            regex_automata::nfa::thompson::NFA::new(regex)
+                .expect("NFA failed")
+                .memory_usage()
+        };
+        assert!(nfa_size(&non_capturing) < nfa_size(&capturing));
+    }
+
+    #[test]
+    fn enum_members_deduplicated_and_sorted() {
+        let schema = json!({"enum": ["b", "a", "b", 1, "a"]});
+        let regex = regex_from_value_with_options(&schema, &GenerationOptions::default())
+            .expect("To regex failed");
+        assert_eq!(regex, r#"("b"|"a"|1)"#);
+
+        let options = GenerationOptions {
+            sort_enum: true,
+            ..GenerationOptions::default()
+        };
+        let sorted = regex_from_value_with_options(&schema, &options).expect("To regex failed");
+        assert_eq!(sorted, r#"("a"|"b"|1)"#);
+        let reordered = json!({"enum": [1, "a", "b"]});
+        assert_eq!(
+            regex_from_value_with_options(&reordered, &options).expect("To regex failed"),
+            sorted
+        );
+    }
+
+    #[test]
+    fn recursive_refs_within_combinators_keep_depth() {
+        let schema = |base: &str| {
+            json!({
+                "$id": "urn:tree",
+                "$defs": {
+                    "a": {
+                        "type": "object",
+                        "properties": {"a": {"$ref": format!("{base}#/$defs/a")}}
+                    },
+                    "b": {
+                        "type": "object",
+                        "properties": {"b": {"$ref": format!("{base}#/$defs/b")}}
+                    }
+                },
+                "oneOf": [
+                    {"$ref": format!("{base}#/$defs/a")},
+                    {"$ref": format!("{base}#/$defs/b")}
+                ]
+            })
+        };
+        let expected = regex_from_value(&schema(""), None, None).expect("To regex failed");
+
+        let with_id = schema("urn:tree");
+        let mut parser = parsing::Parser::new(&with_id);
+        for _ in 0..3 {
+            // Depth leaking between refs or calls would cut the recursion short.
+            assert_eq!(
+                parser.to_regex(&with_id).expect("To regex failed"),
+                expected
+            );
+        }
+        let re = Regex::new(&format!("^{expected}$")).expect("Regex failed");
+        assert!(re.is_match(r#"{"a": {"a": {"a": {}}}}"#));
+        assert!(re.is_match(r#"{"b": {"b": {"b": {}}}}"#));
+    }
+
+    #[test]
+    fn id_qualified_refs_keep_depth() {
+        let schema = json!({
+            "$id": "urn:point",
+            "$defs": {"coordinate": {"type": "integer"}},
+            "type": "object",
+            "properties": {
+                "x": {"$ref": "urn:point#/$defs/coordinate"},
+                "y": {"$ref": "urn:point#/$defs/coordinate"},
+                "z": {"$ref": "urn:point#/$defs/coordinate"},
+                "t": {"$ref": "urn:point#/$defs/coordinate"},
+                "w": {"$ref": "urn:point#/$defs/coordinate"}
+            },
+            "required": ["x", "y", "z", "t", "w"]
+        });
+        let mut parser = parsing::Parser::new(&schema);
+        let regex = parser.to_regex(&schema).expect("To regex failed");
+        let re = Regex::new(&format!("^{regex}$")).expect("Regex failed");
+        assert!(re.is_match(r#"{"x": 1, "y": 2, "z": 3, "t": 4, "w": 5}"#));
+
+        // Failed refs restore the depth as well.
+        for _ in 0..5 {
+            let result = parser.to_regex(&json!({"$ref": "urn:point#missing"}));
+            assert!(
+                matches!(result, Err(Error::AnchorNotFound(_))),
+                "{result:?}"
+            );
+        }
+        assert_eq!(parser.to_regex(&schema).expect("To regex failed"), regex);
+    }
+
+    #[test]
+    fn numeric_bounds_are_over_approximated() {
+        let full_match = |schema: Value| {
+            let regex = regex_from_value(&schema, None, None).expect("To regex failed");
+            Regex::new(&format!("^{regex}$")).expect("Regex failed")
+        };
+
+        let re = full_match(json!({"type": "number", "minimum": 0.0, "maximum": 1.0}));
+        for value in ["0", "0.25", "1", "1.0", "1e-3", "1e+0"] {
+            assert!(re.is_match(value), "{value}");
+        }
+        for value in ["-0.5", "-1", "10", "25.5"] {
+            assert!(!re.is_match(value), "{value}");
+        }
+        // Over-approximation: only the integer part is capped, exponents aren't restricted.
+        assert!(re.is_match("1.5"));
+        assert!(re.is_match("1e+2"));
+
+        let re = full_match(json!({"type": "number", "maximum": 1e25, "minimum": 0}));
+        assert!(re.is_match(&"9".repeat(26)));
+        assert!(!re.is_match(&"9".repeat(27)));
+
+        let re = full_match(json!({"type": "number", "minimum": 0}));
+        assert!(re.is_match("12345.5e+10"));
+        assert!(!re.is_match("-1"));
+
+        let re = full_match(json!({"type": "number", "minimum": -20, "exclusiveMaximum": -1}));
+        assert!(re.is_match("-19.5"));
+        assert!(!re.is_match("5"));
+        assert!(!re.is_match("-100"));
+
+        let re = full_match(json!({"type": "integer", "minimum": -5, "maximum": 150}));
+        for value in ["-5", "0", "150", "999"] {
+            assert!(re.is_match(value), "{value}");
+        }
+        assert!(!re.is_match("1000"));
+        assert!(!re.is_match("1.5"));
+    }
+
+    #[test]
+    fn content_encoding_and_media_type() {
+        let schema = json!({
+            "type": "string",
+            "contentEncoding": "base64",
+            "contentMediaType": "application/json"
+        });
+        let regex = regex_from_value(&schema, None, None).expect("To regex failed");
+        let re = Regex::new(&format!("^{regex}$")).expect("Regex failed");
+        assert!(re.is_match(r#""eyJhIjogMX0=""#));
+        assert!(re.is_match(r#""""#));
+        assert!(!re.is_match(r#""eyJh IjogMX0=""#));
+        assert!(!re.is_match(r#""{\"a\": 1}""#));
+
+        let schema = json!({"type": "string", "contentMediaType": "text/html"});
+        assert_eq!(
+            regex_from_value(&schema, None, None).expect("To regex failed"),
+            STRING
+        );
+        for schema in [
+            json!({"contentMediaType": "text/html"}),
+            json!({"contentEncoding": "base64"}),
+        ] {
+            assert_eq!(
+                regex_from_value(&schema, None, None).expect("To regex failed"),
+                regex_from_value(&json!({}), None, None).expect("To regex failed")
+            );
+        }
+    }
+
+    #[test]
+    fn max_properties_zero_allows_only_empty_object() {
+        for schema in [
+            json!({"type": "object", "maxProperties": 0}),
+            json!({"type": "object", "maxProperties": 0, "additionalProperties": {"type": "integer"}}),
+        ] {
+            let regex = regex_from_value(&schema, None, None).expect("To regex failed");
+            let re = Regex::new(&format!("^{regex}$")).expect("Regex failed");
+            assert!(re.is_match("{}"));
+            assert!(re.is_match("{ }"));
+            assert!(!re.is_match(r#"{"a": 1}"#));
+        }
+
+        let unconstrained = json!({"type": "object"});
+        let regex = regex_from_value(&unconstrained, None, None).expect("To regex failed");
+        let re = Regex::new(&format!("^{regex}$")).expect("Regex failed");
+        assert!(re.is_match("{}"));
+        assert!(re.is_match(r#"{"a": 1}"#));
+
+        let unsatisfiable = json!({"type": "object", "minProperties": 1, "maxProperties": 0});
+        assert!(matches!(
+            regex_from_value(&unsatisfiable, None, None),
+            Err(Error::UnsatisfiablePropertyCount)
+        ));
+    }
 }
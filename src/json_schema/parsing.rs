@@ -3,16 +3,46 @@
 use std::num::NonZeroU64;
 
 use regex::escape;
+use rustc_hash::FxHashSet as HashSet;
 use serde_json::{json, Value};
 
-use crate::json_schema::types;
+use crate::json_schema::{types, PropertyOrder, DEFAULT_UNCONSTRAINED_DEPTH};
 use crate::{Error, Result};
 
+/// Keywords that only annotate a schema and never affect the generated regex.
+pub(crate) const ANNOTATION_KEYWORDS: [&str; 9] = [
+    "title",
+    "description",
+    "readOnly",
+    "writeOnly",
+    "default",
+    "examples",
+    "deprecated",
+    "$comment",
+    "contentMediaType",
+];
+
+/// Limit of optional properties, all combinations of which are enumerated to enforce
+/// `minProperties` and `maxProperties` along with `properties`.
+const MAX_OPTIONAL_PROPERTIES_FOR_COUNT: usize = 10;
+
+/// Limit of properties, all permutations of which are enumerated for [`PropertyOrder::AnyOrder`].
+const MAX_PROPERTIES_FOR_ANY_ORDER: usize = 6;
+
 pub(crate) struct Parser<'a> {
     root: &'a Value,
     whitespace_pattern: &'a str,
     recursion_depth: usize,
     max_recursion_depth: usize,
+    property_order: PropertyOrder,
+    unconstrained_depth: u64,
+    integer_pattern: Option<&'a str>,
+    number_pattern: Option<&'a str>,
+    allow_control_chars_in_strings: bool,
+    bracket_whitespace: Option<&'a str>,
+    separator_whitespace: Option<&'a str>,
+    strict_one_of: bool,
+    sort_enum: bool,
 }
 
 impl<'a> Parser<'a> {
@@ -29,6 +59,15 @@ impl<'a> Parser<'a> {
             whitespace_pattern: types::WHITESPACE,
             recursion_depth: 0,
             max_recursion_depth: 3,
+            property_order: PropertyOrder::Strict,
+            unconstrained_depth: DEFAULT_UNCONSTRAINED_DEPTH as u64,
+            integer_pattern: None,
+            number_pattern: None,
+            allow_control_chars_in_strings: false,
+            bracket_whitespace: None,
+            separator_whitespace: None,
+            strict_one_of: false,
+            sort_enum: false,
         }
     }
 
@@ -46,25 +85,159 @@ impl<'a> Parser<'a> {
         }
     }
 
+    pub fn with_property_order(self, property_order: PropertyOrder) -> Self {
+        Self {
+            property_order,
+            ..self
+        }
+    }
+
+    pub fn with_unconstrained_depth(self, unconstrained_depth: usize) -> Self {
+        Self {
+            unconstrained_depth: unconstrained_depth as u64,
+            ..self
+        }
+    }
+
+    pub fn with_integer_pattern(self, integer_pattern: &'a str) -> Self {
+        Self {
+            integer_pattern: Some(integer_pattern),
+            ..self
+        }
+    }
+
+    pub fn with_number_pattern(self, number_pattern: &'a str) -> Self {
+        Self {
+            number_pattern: Some(number_pattern),
+            ..self
+        }
+    }
+
+    pub fn with_control_chars_in_strings(self, allow_control_chars_in_strings: bool) -> Self {
+        Self {
+            allow_control_chars_in_strings,
+            ..self
+        }
+    }
+
+    pub fn with_bracket_whitespace(self, bracket_whitespace: &'a str) -> Self {
+        Self {
+            bracket_whitespace: Some(bracket_whitespace),
+            ..self
+        }
+    }
+
+    pub fn with_separator_whitespace(self, separator_whitespace: &'a str) -> Self {
+        Self {
+            separator_whitespace: Some(separator_whitespace),
+            ..self
+        }
+    }
+
+    pub fn with_strict_one_of(self, strict_one_of: bool) -> Self {
+        Self {
+            strict_one_of,
+            ..self
+        }
+    }
+
+    pub fn with_sort_enum(self, sort_enum: bool) -> Self {
+        Self { sort_enum, ..self }
+    }
+
+    /// Whitespace right inside of array and object brackets.
+    fn bracket_whitespace(&self) -> &'a str {
+        self.bracket_whitespace.unwrap_or(self.whitespace_pattern)
+    }
+
+    /// Whitespace around commas between items of arrays and objects.
+    fn separator_whitespace(&self) -> &'a str {
+        self.separator_whitespace.unwrap_or(self.whitespace_pattern)
+    }
+
+    fn string_inner(&self) -> &'static str {
+        if self.allow_control_chars_in_strings {
+            types::STRING_INNER_WITH_CONTROL_CHARS
+        } else {
+            types::STRING_INNER
+        }
+    }
+
+    fn string(&self) -> &'static str {
+        if self.allow_control_chars_in_strings {
+            types::STRING_WITH_CONTROL_CHARS
+        } else {
+            types::STRING
+        }
+    }
+
+    /// Checks that a user-supplied pattern compiles.
+    pub fn validate_pattern(pattern: &str) -> Result<()> {
+        match regex::Regex::new(pattern) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Error::InvalidPattern {
+                pattern: Box::from(pattern),
+                source: Box::new(e),
+            }),
+        }
+    }
+
     #[allow(clippy::wrong_self_convention)]
     pub fn to_regex(&mut self, json: &Value) -> Result<String> {
+        let mut regex = String::new();
+        self.to_regex_into(json, &mut regex)?;
+        Ok(regex)
+    }
+
+    /// Appends the regex of the schema to `regex`, rather than allocating a new string.
+    ///
+    /// Composite schemas write their parts straight into the same buffer, only the parts,
+    /// which are repeated within a pattern, are built separately. On error the content
+    /// of `regex` is unspecified.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_regex_into(&mut self, json: &Value, regex: &mut String) -> Result<()> {
         match json {
-            Value::Object(obj) if obj.is_empty() => self.parse_empty_object(),
-            Value::Object(obj) if obj.contains_key("properties") => self.parse_properties(obj),
-            Value::Object(obj) if obj.contains_key("allOf") => self.parse_all_of(obj),
-            Value::Object(obj) if obj.contains_key("anyOf") => self.parse_any_of(obj),
-            Value::Object(obj) if obj.contains_key("oneOf") => self.parse_one_of(obj),
-            Value::Object(obj) if obj.contains_key("prefixItems") => self.parse_prefix_items(obj),
-            Value::Object(obj) if obj.contains_key("enum") => self.parse_enum(obj),
-            Value::Object(obj) if obj.contains_key("const") => self.parse_const(obj),
-            Value::Object(obj) if obj.contains_key("$ref") => self.parse_ref(obj),
-            Value::Object(obj) if obj.contains_key("type") => self.parse_type(obj),
+            // Boolean schemas: `true` allows anything and `false` allows nothing.
+            Value::Bool(true) => {
+                // Grouped, so the alternation stays intact within other patterns.
+                regex.push('(');
+                self.parse_empty_object(regex)?;
+                regex.push(')');
+                Ok(())
+            }
+            Value::Bool(false) => {
+                regex.push_str(types::NOTHING);
+                Ok(())
+            }
+            Value::Object(obj) if Self::is_unconstrained(obj) => self.parse_empty_object(regex),
+            Value::Object(obj) if obj.contains_key("properties") => {
+                regex.push_str(&self.parse_properties(obj)?);
+                Ok(())
+            }
+            Value::Object(obj) if obj.contains_key("allOf") => self.parse_all_of(obj, regex),
+            Value::Object(obj) if obj.contains_key("anyOf") => self.parse_any_of(obj, regex),
+            Value::Object(obj) if obj.contains_key("oneOf") => self.parse_one_of(obj, regex),
+            Value::Object(obj) if obj.contains_key("prefixItems") => {
+                self.parse_prefix_items(obj, regex)
+            }
+            Value::Object(obj) if obj.contains_key("enum") => self.parse_enum(obj, regex),
+            Value::Object(obj) if obj.contains_key("const") => self.parse_const(obj, regex),
+            Value::Object(obj) if obj.contains_key("$ref") => self.parse_ref(obj, regex),
+            Value::Object(obj) if obj.contains_key("type") => self.parse_type(obj, regex),
             json => Err(Error::UnsupportedJsonSchema(Box::new(json.clone()))),
         }
     }
 
-    fn parse_empty_object(&mut self) -> Result<String> {
+    fn is_unconstrained(obj: &serde_json::Map<String, Value>) -> bool {
+        // `contentEncoding` only restricts strings, so it alone doesn't constrain the type.
+        obj.keys().all(|key| {
+            ANNOTATION_KEYWORDS.contains(&key.as_str()) || key.as_str() == "contentEncoding"
+        })
+    }
+
+    fn parse_empty_object(&mut self, regex: &mut String) -> Result<()> {
         // JSON Schema Spec: Empty object means unconstrained, any json type is legal
+        #[allow(clippy::useless_vec)]
         let types = vec![
             json!({"type": "boolean"}),
             json!({"type": "null"}),
@@ -74,20 +247,19 @@ impl<'a> Parser<'a> {
             json!({"type": "array"}),
             json!({"type": "object"}),
         ];
-        let regex = types
-            .iter()
-            .try_fold(Vec::with_capacity(types.len()), |mut acc, object| {
-                self.to_regex(object).map(|string| {
-                    acc.push(format!("({})", string));
-                    acc
-                })
-            })?
-            .join("|");
-        Ok(regex)
+        for (i, object) in types.iter().enumerate() {
+            if i > 0 {
+                regex.push('|');
+            }
+            regex.push('(');
+            self.to_regex_into(object, regex)?;
+            regex.push(')');
+        }
+        Ok(())
     }
 
     fn parse_properties(&mut self, obj: &serde_json::Map<String, Value>) -> Result<String> {
-        let mut regex = String::from(r"\{");
+        let mut regex = String::new();
 
         let properties = obj
             .get("properties")
@@ -100,28 +272,44 @@ impl<'a> Parser<'a> {
             .map(|arr| arr.iter().filter_map(Value::as_str).collect::<Vec<_>>())
             .unwrap_or_default();
 
+        let min_properties = obj.get("minProperties").and_then(Value::as_u64);
+        let max_properties = obj.get("maxProperties").and_then(Value::as_u64);
+        if min_properties.is_some()
+            || max_properties.is_some()
+            || self.property_order == PropertyOrder::AnyOrder
+        {
+            return self.parse_properties_combinations(
+                obj,
+                properties,
+                &required_properties,
+                min_properties,
+                max_properties,
+            );
+        }
+
         let is_required: Vec<bool> = properties
             .keys()
             .map(|item| required_properties.contains(&item.as_str()))
             .collect();
 
-        if is_required.iter().any(|&x| x) {
+        let has_required = is_required.iter().any(|&x| x);
+        if has_required {
             let last_required_pos = is_required
                 .iter()
                 .enumerate()
                 .filter(|&(_, &value)| value)
                 .map(|(i, _)| i)
                 .max()
-                .unwrap();
+                .unwrap_or_default();
 
             for (i, (name, value)) in properties.iter().enumerate() {
                 let mut subregex =
                     format!(r#"{0}"{1}"{0}:{0}"#, self.whitespace_pattern, escape(name));
-                subregex += &mut match self.to_regex(value) {
-                    Ok(regex) => regex,
+                match self.to_regex_into(value, &mut subregex) {
+                    Ok(()) => {}
                     Err(e) if e.is_recursion_limit() => continue,
                     Err(e) => return Err(e),
-                };
+                }
                 match i {
                     i if i < last_required_pos => {
                         subregex = format!("{}{},", subregex, self.whitespace_pattern)
@@ -142,11 +330,11 @@ impl<'a> Parser<'a> {
             for (name, value) in properties.iter() {
                 let mut subregex =
                     format!(r#"{0}"{1}"{0}:{0}"#, self.whitespace_pattern, escape(name));
-                subregex += &mut match self.to_regex(value) {
-                    Ok(regex) => regex,
+                match self.to_regex_into(value, &mut subregex) {
+                    Ok(()) => {}
                     Err(e) if e.is_recursion_limit() => continue,
                     Err(e) => return Err(e),
-                };
+                }
                 property_subregexes.push(subregex);
             }
 
@@ -160,194 +348,563 @@ impl<'a> Parser<'a> {
                 possible_patterns.push(pattern);
             }
 
-            regex += &format!("({})?", possible_patterns.join("|"));
+            regex += &possible_patterns.join("|");
         }
 
-        regex += &format!("{}\\}}", self.whitespace_pattern);
-        Ok(regex)
+        self.close_properties(obj, regex, !has_required)
     }
 
-    fn parse_all_of(&mut self, obj: &serde_json::Map<String, Value>) -> Result<String> {
-        match obj.get("allOf") {
-            Some(Value::Array(all_of)) => {
-                let subregexes: Result<Vec<String>> =
-                    all_of.iter().map(|t| self.to_regex(t)).collect();
+    /// Wraps the pattern of properties into an object, followed by additional properties,
+    /// if `additionalProperties` allows them.
+    ///
+    /// Keys of additional properties are any strings, even the ones of declared properties.
+    fn close_properties(
+        &mut self,
+        obj: &serde_json::Map<String, Value>,
+        pattern: String,
+        allows_empty: bool,
+    ) -> Result<String> {
+        let additional_value = match obj.get("additionalProperties") {
+            None | Some(Value::Bool(false)) => None,
+            Some(Value::Bool(true)) => {
+                let mut regex = String::new();
+                self.parse_empty_object(&mut regex)?;
+                Some(regex)
+            }
+            Some(schema) => Some(self.to_regex(schema)?),
+        };
+
+        let inner = match additional_value {
+            None if allows_empty => format!("({})?", pattern),
+            None => pattern,
+            Some(value) => {
+                let additional = format!(
+                    "{0}{1}{0}:{0}({2})",
+                    self.whitespace_pattern,
+                    self.string(),
+                    value
+                );
+                let tail = format!("({},{})*", self.whitespace_pattern, additional);
+                if !allows_empty {
+                    format!("{}{}", pattern, tail)
+                } else if pattern.is_empty() {
+                    format!("({}{})?", additional, tail)
+                } else {
+                    format!("(({}){}|{}{})?", pattern, tail, additional, tail)
+                }
+            }
+        };
+        Ok(format!(r"\{{{}{}\}}", inner, self.whitespace_pattern))
+    }
+
+    /// Alternates over every combination of optional properties, which satisfies property
+    /// count bounds, and over every order of them for [`PropertyOrder::AnyOrder`].
+    ///
+    /// The number of combinations grows exponentially, so it's capped by
+    /// [`MAX_OPTIONAL_PROPERTIES_FOR_COUNT`] optional properties, and the number of permutations
+    /// grows even faster, so it's capped by [`MAX_PROPERTIES_FOR_ANY_ORDER`] properties.
+    fn parse_properties_combinations(
+        &mut self,
+        obj: &serde_json::Map<String, Value>,
+        properties: &serde_json::Map<String, Value>,
+        required_properties: &[&str],
+        min_properties: Option<u64>,
+        max_properties: Option<u64>,
+    ) -> Result<String> {
+        if let (Some(min), Some(max)) = (min_properties, max_properties) {
+            if min > max {
+                return Err(Error::MaxBoundError);
+            }
+        }
 
-                let subregexes = subregexes?;
-                let combined_regex = subregexes.join("");
+        let mut property_subregexes = Vec::new();
+        for (name, value) in properties.iter() {
+            let mut subregex = format!(r#"{0}"{1}"{0}:{0}"#, self.whitespace_pattern, escape(name));
+            match self.to_regex_into(value, &mut subregex) {
+                Ok(()) => {}
+                Err(e) if e.is_recursion_limit() => continue,
+                Err(e) => return Err(e),
+            }
+            property_subregexes.push((required_properties.contains(&name.as_str()), subregex));
+        }
+
+        let any_order = self.property_order == PropertyOrder::AnyOrder;
+        if any_order && property_subregexes.len() > MAX_PROPERTIES_FOR_ANY_ORDER {
+            return Err(Error::TooManyPropertiesForAnyOrder(
+                property_subregexes.len(),
+                MAX_PROPERTIES_FOR_ANY_ORDER,
+            ));
+        }
 
-                Ok(format!(r"({})", combined_regex))
+        let num_optional = property_subregexes
+            .iter()
+            .filter(|(is_required, _)| !is_required)
+            .count();
+        if num_optional > MAX_OPTIONAL_PROPERTIES_FOR_COUNT {
+            return Err(Error::TooManyOptionalPropertiesForCount(
+                num_optional,
+                MAX_OPTIONAL_PROPERTIES_FOR_COUNT,
+            ));
+        }
+        let num_required = property_subregexes.len() - num_optional;
+
+        let separator = format!("{},", self.whitespace_pattern);
+        let mut possible_patterns = Vec::new();
+        for mask in 0u32..(1 << num_optional) {
+            let count = (num_required + mask.count_ones() as usize) as u64;
+            if min_properties.is_some_and(|min| count < min)
+                || max_properties.is_some_and(|max| count > max)
+            {
+                continue;
+            }
+            let mut optional_index = 0;
+            let present: Vec<&str> = property_subregexes
+                .iter()
+                .filter(|(is_required, _)| {
+                    *is_required || {
+                        optional_index += 1;
+                        mask & (1 << (optional_index - 1)) != 0
+                    }
+                })
+                .map(|(_, subregex)| subregex.as_str())
+                .collect();
+            if any_order {
+                for permutation in Self::permutations(&present) {
+                    possible_patterns.push(permutation.join(&separator));
+                }
+            } else {
+                possible_patterns.push(present.join(&separator));
+            }
+        }
+
+        if possible_patterns.is_empty() {
+            return Err(Error::UnsatisfiablePropertyCount);
+        }
+        let allows_empty = possible_patterns.first().is_some_and(String::is_empty);
+        if allows_empty {
+            possible_patterns.remove(0);
+            self.close_properties(obj, possible_patterns.join("|"), true)
+        } else {
+            self.close_properties(obj, format!("({})", possible_patterns.join("|")), false)
+        }
+    }
+
+    fn permutations<'b>(items: &[&'b str]) -> Vec<Vec<&'b str>> {
+        if items.len() <= 1 {
+            return vec![items.to_vec()];
+        }
+        let mut permutations = Vec::new();
+        for (i, item) in items.iter().enumerate() {
+            let mut rest = items.to_vec();
+            rest.remove(i);
+            for mut permutation in Self::permutations(&rest) {
+                permutation.insert(0, item);
+                permutations.push(permutation);
+            }
+        }
+        permutations
+    }
+
+    /// Appends regexes of `schemas` to `regex`, each one preceded by `prefix`, followed by
+    /// `suffix` and separated by `separator`.
+    fn join_into(
+        &mut self,
+        schemas: &[Value],
+        (prefix, separator, suffix): (&str, &str, &str),
+        regex: &mut String,
+    ) -> Result<()> {
+        for (i, schema) in schemas.iter().enumerate() {
+            if i > 0 {
+                regex.push_str(separator);
+            }
+            regex.push_str(prefix);
+            self.to_regex_into(schema, regex)?;
+            regex.push_str(suffix);
+        }
+        Ok(())
+    }
+
+    fn parse_all_of(
+        &mut self,
+        obj: &serde_json::Map<String, Value>,
+        regex: &mut String,
+    ) -> Result<()> {
+        match obj.get("allOf") {
+            Some(Value::Array(all_of)) => {
+                regex.push('(');
+                self.join_into(all_of, ("", "", ""), regex)?;
+                regex.push(')');
+                Ok(())
             }
             _ => Err(Error::AllOfMustBeAnArray),
         }
     }
 
-    fn parse_any_of(&mut self, obj: &serde_json::Map<String, Value>) -> Result<String> {
+    fn parse_any_of(
+        &mut self,
+        obj: &serde_json::Map<String, Value>,
+        regex: &mut String,
+    ) -> Result<()> {
         match obj.get("anyOf") {
             Some(Value::Array(any_of)) => {
-                let subregexes: Result<Vec<String>> =
-                    any_of.iter().map(|t| self.to_regex(t)).collect();
-
-                let subregexes = subregexes?;
-
-                Ok(format!(r"({})", subregexes.join("|")))
+                regex.push('(');
+                self.join_into(any_of, ("", "|", ""), regex)?;
+                regex.push(')');
+                Ok(())
             }
             _ => Err(Error::AnyOfMustBeAnArray),
         }
     }
 
-    fn parse_one_of(&mut self, obj: &serde_json::Map<String, Value>) -> Result<String> {
+    fn parse_one_of(
+        &mut self,
+        obj: &serde_json::Map<String, Value>,
+        regex: &mut String,
+    ) -> Result<()> {
         match obj.get("oneOf") {
             Some(Value::Array(one_of)) => {
-                let subregexes: Result<Vec<String>> =
-                    one_of.iter().map(|t| self.to_regex(t)).collect();
-
-                let subregexes = subregexes?;
-                let xor_patterns: Vec<String> = subregexes
-                    .into_iter()
-                    .map(|subregex| format!(r"(?:{})", subregex))
-                    .collect();
-
-                Ok(format!(r"({})", xor_patterns.join("|")))
+                // Integers are numbers as well, so the `number` branch is left with
+                // non-integers only, when both branches are present. A custom number
+                // pattern is kept as it is, since integers can't be excluded from it.
+                let exclude_integers = self.strict_one_of
+                    && self.number_pattern.is_none()
+                    && one_of
+                        .iter()
+                        .any(|schema| Self::is_plain_type(schema, "integer"));
+                regex.push('(');
+                for (i, schema) in one_of.iter().enumerate() {
+                    if i > 0 {
+                        regex.push('|');
+                    }
+                    regex.push_str("(?:");
+                    if exclude_integers && Self::is_plain_type(schema, "number") {
+                        regex.push_str(types::NON_INTEGER_NUMBER);
+                    } else {
+                        self.to_regex_into(schema, regex)?;
+                    }
+                    regex.push(')');
+                }
+                regex.push(')');
+                Ok(())
             }
             _ => Err(Error::OneOfMustBeAnArray),
         }
     }
 
-    fn parse_prefix_items(&mut self, obj: &serde_json::Map<String, Value>) -> Result<String> {
+    /// Checks if the schema is just the type, without any constraints.
+    fn is_plain_type(schema: &Value, instance_type: &str) -> bool {
+        schema.as_object().is_some_and(|obj| {
+            obj.get("type").and_then(Value::as_str) == Some(instance_type)
+                && obj
+                    .keys()
+                    .all(|key| key == "type" || ANNOTATION_KEYWORDS.contains(&key.as_str()))
+        })
+    }
+
+    fn parse_prefix_items(
+        &mut self,
+        obj: &serde_json::Map<String, Value>,
+        regex: &mut String,
+    ) -> Result<()> {
         match obj.get("prefixItems") {
             Some(Value::Array(prefix_items)) => {
-                let element_patterns: Result<Vec<String>> =
-                    prefix_items.iter().map(|t| self.to_regex(t)).collect();
+                let bracket_whitespace = self.bracket_whitespace();
+                let comma_split_pattern = format!("{0},{0}", self.separator_whitespace());
+                let tail = self.prefix_items_tail(obj, prefix_items.len(), &comma_split_pattern)?;
+
+                regex.push_str(r"\[");
+                regex.push_str(bracket_whitespace);
+                self.join_into(prefix_items, ("", &comma_split_pattern, ""), regex)?;
+                regex.push_str(&tail);
+                regex.push_str(bracket_whitespace);
+                regex.push_str(r"\]");
+                Ok(())
+            }
+            _ => Err(Error::PrefixItemsMustBeAnArray),
+        }
+    }
 
-                let element_patterns = element_patterns?;
+    /// Builds the regex of items following `prefixItems`, constrained by the `items` schema.
+    ///
+    /// `minItems` and `maxItems` bound the whole array, so the tail gets what's left of them
+    /// after the prefix. Without `items` schema, or with `items: false`, there is no tail
+    /// and the array is exactly as long as the prefix.
+    fn prefix_items_tail(
+        &mut self,
+        obj: &serde_json::Map<String, Value>,
+        prefix_len: usize,
+        comma_split_pattern: &str,
+    ) -> Result<String> {
+        let min_items = obj.get("minItems").and_then(Value::as_u64).unwrap_or(0);
+        let max_items = obj.get("maxItems").and_then(Value::as_u64);
+        let items = match obj.get("items") {
+            Some(items @ (Value::Object(_) | Value::Bool(true))) => Some(items),
+            _ => None,
+        };
 
-                let comma_split_pattern = format!("{0},{0}", self.whitespace_pattern);
-                let tuple_inner = element_patterns.join(&comma_split_pattern);
+        let prefix = prefix_len as u64;
+        let is_satisfiable = match (items, max_items) {
+            (_, Some(max_items)) if max_items < min_items || max_items < prefix => false,
+            (None, _) => min_items <= prefix,
+            _ => true,
+        };
+        if !is_satisfiable {
+            return Err(Error::UnsatisfiableItemCount(prefix_len));
+        }
 
-                Ok(format!(r"\[{0}{tuple_inner}{0}\]", self.whitespace_pattern))
-            }
-            _ => Err(Error::PrefixItemsMustBeAnArray),
+        let min_tail = min_items.saturating_sub(prefix);
+        let max_tail = max_items.map(|max_items| max_items - prefix);
+        let Some(items) = items.filter(|_| max_tail != Some(0)) else {
+            return Ok(String::new());
+        };
+
+        let items_regex = self.to_regex(items)?;
+        let repeats = |min: u64, max: Option<u64>| match max {
+            Some(max) => format!("{{{min},{max}}}"),
+            None => format!("{{{min},}}"),
+        };
+        if prefix_len > 0 {
+            Ok(format!(
+                "({comma_split_pattern}({items_regex})){}",
+                repeats(min_tail, max_tail)
+            ))
+        } else {
+            // The first item isn't preceded by a separator.
+            Ok(format!(
+                "(({items_regex})({comma_split_pattern}({items_regex})){}){}",
+                repeats(
+                    min_tail.saturating_sub(1),
+                    max_tail.map(|max_tail| max_tail - 1)
+                ),
+                if min_tail == 0 { "?" } else { "" }
+            ))
         }
     }
 
-    fn parse_enum(&mut self, obj: &serde_json::Map<String, Value>) -> Result<String> {
+    fn parse_enum(
+        &mut self,
+        obj: &serde_json::Map<String, Value>,
+        regex: &mut String,
+    ) -> Result<()> {
         match obj.get("enum") {
             Some(Value::Array(enum_values)) => {
-                let choices: Result<Vec<String>> = enum_values
+                Self::check_enum_types(enum_values, obj)?;
+                // Identical members would only produce redundant alternatives.
+                let mut seen = HashSet::default();
+                let mut members: Vec<(String, &Value)> = enum_values
                     .iter()
-                    .map(|choice| self.parse_const_value(choice))
+                    .map(|value| (value.to_string(), value))
+                    .filter(|(serialized, _)| seen.insert(serialized.clone()))
                     .collect();
-
-                let choices = choices?;
-                Ok(format!(r"({})", choices.join("|")))
+                if self.sort_enum {
+                    members.sort_by(|(a, _), (b, _)| a.cmp(b));
+                }
+                regex.push('(');
+                for (i, (_, choice)) in members.into_iter().enumerate() {
+                    if i > 0 {
+                        regex.push('|');
+                    }
+                    self.parse_const_value(choice, regex)?;
+                }
+                regex.push(')');
+                Ok(())
             }
             _ => Err(Error::EnumMustBeAnArray),
         }
     }
 
-    fn parse_const(&mut self, obj: &serde_json::Map<String, Value>) -> Result<String> {
+    /// Ensures every `enum` member is an instance of the declared `type`, if any.
+    ///
+    /// Types, which aren't standard JSON Schema types, are left for `parse_type` to report.
+    fn check_enum_types(enum_values: &[Value], obj: &serde_json::Map<String, Value>) -> Result<()> {
+        let instance_types: Vec<&str> = match obj.get("type") {
+            Some(Value::String(instance_type)) => vec![instance_type.as_str()],
+            Some(Value::Array(instance_types)) => {
+                instance_types.iter().filter_map(Value::as_str).collect()
+            }
+            _ => return Ok(()),
+        };
+        let is_instance = |value: &Value, instance_type: &str| -> Option<bool> {
+            let is_instance = match instance_type {
+                "string" => value.is_string(),
+                "integer" => value.as_f64().is_some_and(|number| number.fract() == 0.0),
+                "number" => value.is_number(),
+                "boolean" => value.is_boolean(),
+                "null" => value.is_null(),
+                "array" => value.is_array(),
+                "object" => value.is_object(),
+                _ => return None,
+            };
+            Some(is_instance)
+        };
+        for value in enum_values {
+            let mut matches = false;
+            for instance_type in &instance_types {
+                match is_instance(value, instance_type) {
+                    Some(is_instance) => matches |= is_instance,
+                    None => return Ok(()),
+                }
+            }
+            if !matches {
+                return Err(Error::EnumTypeMismatch {
+                    value: Box::new(value.clone()),
+                    instance_type: Box::from(instance_types.join(", ")),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_const(
+        &mut self,
+        obj: &serde_json::Map<String, Value>,
+        regex: &mut String,
+    ) -> Result<()> {
         if let Some(const_value) = obj.get("const") {
-            self.parse_const_value(const_value)
+            self.parse_const_value(const_value, regex)
         } else {
             Err(Error::ConstKeyNotFound)
         }
     }
 
-    fn parse_const_value(&self, value: &Value) -> Result<String> {
+    fn parse_const_value(&self, value: &Value, regex: &mut String) -> Result<()> {
         match value {
             Value::Array(array_values) => {
-                let inner_regex = array_values
-                    .iter()
-                    .map(|value| self.parse_const_value(value))
-                    .collect::<Result<Vec<String>>>()?
-                    .join(format!("{0},{0}", self.whitespace_pattern).as_str());
-                Ok(format!(r"\[{0}{inner_regex}{0}\]", self.whitespace_pattern))
+                regex.push_str(r"\[");
+                regex.push_str(self.whitespace_pattern);
+                for (i, value) in array_values.iter().enumerate() {
+                    if i > 0 {
+                        regex.push_str(self.whitespace_pattern);
+                        regex.push(',');
+                        regex.push_str(self.whitespace_pattern);
+                    }
+                    self.parse_const_value(value, regex)?;
+                }
+                regex.push_str(self.whitespace_pattern);
+                regex.push_str(r"\]");
             }
             Value::Object(obj) => {
-                let inner_regex = obj
-                    .iter()
-                    .map(|(key, value)| {
-                        self.parse_const_value(value).map(|value_regex| {
-                            format!(r#""{key}"{0}:{0}{value_regex}"#, self.whitespace_pattern)
-                        })
-                    })
-                    .collect::<Result<Vec<String>>>()?
-                    .join(format!("{0},{0}", self.whitespace_pattern).as_str());
-                Ok(format!(
-                    r"\{{{0}{inner_regex}{0}\}}",
-                    self.whitespace_pattern
-                ))
+                regex.push_str(r"\{");
+                regex.push_str(self.whitespace_pattern);
+                for (i, (key, value)) in obj.iter().enumerate() {
+                    if i > 0 {
+                        regex.push_str(self.whitespace_pattern);
+                        regex.push(',');
+                        regex.push_str(self.whitespace_pattern);
+                    }
+                    regex.push_str(&regex::escape(&serde_json::to_string(key)?));
+                    regex.push_str(self.whitespace_pattern);
+                    regex.push(':');
+                    regex.push_str(self.whitespace_pattern);
+                    self.parse_const_value(value, regex)?;
+                }
+                regex.push_str(self.whitespace_pattern);
+                regex.push_str(r"\}");
             }
             _ => {
                 let json_string = serde_json::to_string(value)?;
-                Ok(regex::escape(&json_string))
+                regex.push_str(&regex::escape(&json_string));
             }
         }
+        Ok(())
     }
 
-    fn parse_ref(&mut self, obj: &serde_json::Map<String, Value>) -> Result<String> {
+    fn parse_ref(
+        &mut self,
+        obj: &serde_json::Map<String, Value>,
+        regex: &mut String,
+    ) -> Result<()> {
         if self.recursion_depth > self.max_recursion_depth {
             return Err(Error::RefRecursionLimitReached(self.max_recursion_depth));
         }
         self.recursion_depth += 1;
-        let ref_path = obj["$ref"]
-            .as_str()
+        // Resolved separately, so the depth is restored on every path, including errors.
+        let result = self.parse_ref_path(obj, regex);
+        self.recursion_depth -= 1;
+        result
+    }
+
+    fn parse_ref_path(
+        &mut self,
+        obj: &serde_json::Map<String, Value>,
+        regex: &mut String,
+    ) -> Result<()> {
+        let ref_path = obj
+            .get("$ref")
+            .and_then(Value::as_str)
             .ok_or_else(|| Error::RefMustBeAString)?;
 
         let parts: Vec<&str> = ref_path.split('#').collect();
 
-        let result = match parts.as_slice() {
+        match parts.as_slice() {
             [fragment] | ["", fragment] => {
-                let path_parts: Vec<&str> =
-                    fragment.split('/').filter(|&s| !s.is_empty()).collect();
-                let referenced_schema = Self::resolve_local_ref(self.root, &path_parts)?;
-                self.to_regex(referenced_schema)
-            }
-            [base, fragment] => {
-                if let Some(id) = self.root["$id"].as_str() {
-                    if *base == id || base.is_empty() {
-                        let path_parts: Vec<&str> =
-                            fragment.split('/').filter(|&s| !s.is_empty()).collect();
-                        let referenced_schema = Self::resolve_local_ref(self.root, &path_parts)?;
-                        return self.to_regex(referenced_schema);
-                    }
-                }
-                Err(Error::ExternalReferencesNotSupported(Box::from(ref_path)))
+                let referenced_schema = self.resolve_fragment(fragment)?;
+                self.to_regex_into(referenced_schema, regex)
             }
+            [base, fragment] => match self.root.get("$id").and_then(Value::as_str) {
+                Some(id) if *base == id || base.is_empty() => {
+                    let referenced_schema = self.resolve_fragment(fragment)?;
+                    self.to_regex_into(referenced_schema, regex)
+                }
+                _ => Err(Error::ExternalReferencesNotSupported(Box::from(ref_path))),
+            },
             _ => Err(Error::InvalidReferenceFormat(Box::from(ref_path))),
-        };
-        self.recursion_depth -= 1;
-        result
+        }
     }
 
-    fn parse_type(&mut self, obj: &serde_json::Map<String, Value>) -> Result<String> {
+    fn parse_type(
+        &mut self,
+        obj: &serde_json::Map<String, Value>,
+        regex: &mut String,
+    ) -> Result<()> {
         match obj.get("type") {
-            Some(Value::String(instance_type)) => self.parse_type_string(instance_type, obj),
-            Some(Value::Array(instance_types)) => self.parse_type_array(instance_types, obj),
+            Some(Value::String(instance_type)) => {
+                Self::check_format_applies(&[instance_type.as_str()], obj)?;
+                regex.push_str(&self.parse_type_string(instance_type, obj)?);
+                Ok(())
+            }
+            Some(Value::Array(instance_types)) => {
+                let types: Vec<&str> = instance_types.iter().filter_map(Value::as_str).collect();
+                Self::check_format_applies(&types, obj)?;
+                self.parse_type_array(instance_types, obj, regex)
+            }
             _ => Err(Error::TypeMustBeAStringOrArray),
         }
     }
 
+    /// Ensures `format` is only used along with the `string` type, the only one it applies to.
+    fn check_format_applies(
+        instance_types: &[&str],
+        obj: &serde_json::Map<String, Value>,
+    ) -> Result<()> {
+        if obj.contains_key("format") && !instance_types.contains(&"string") {
+            return Err(Error::FormatOnNonString(Box::from(
+                instance_types.join(", "),
+            )));
+        }
+        Ok(())
+    }
+
     fn parse_type_array(
         &mut self,
         instance_types: &[serde_json::Value],
         obj: &serde_json::Map<String, Value>,
-    ) -> Result<String> {
-        let xor_patterns = instance_types
-            .iter()
-            .map(|instance_type| match instance_type.as_str() {
-                Some(instance_type) => {
-                    let sub_regex = self.parse_type_string(instance_type, obj)?;
-
-                    Ok(format!(r"(?:{})", sub_regex))
-                }
-                None => Err(Error::TypeMustBeAStringOrArray),
-            })
-            .collect::<Result<Vec<String>>>()?
-            .join("|");
-
-        Ok(format!(r"({})", xor_patterns))
+        regex: &mut String,
+    ) -> Result<()> {
+        regex.push('(');
+        for (i, instance_type) in instance_types.iter().enumerate() {
+            let Some(instance_type) = instance_type.as_str() else {
+                return Err(Error::TypeMustBeAStringOrArray);
+            };
+            if i > 0 {
+                regex.push('|');
+            }
+            regex.push_str("(?:");
+            regex.push_str(&self.parse_type_string(instance_type, obj)?);
+            regex.push(')');
+        }
+        regex.push(')');
+        Ok(())
     }
 
     fn parse_type_string(
@@ -357,12 +914,21 @@ impl<'a> Parser<'a> {
     ) -> Result<String> {
         match instance_type {
             "string" => self.parse_string_type(obj),
-            "number" => self.parse_number_type(obj),
+            // `float` and `decimal` are non-standard aliases of `number`, emitted by some tools.
+            "number" | "float" | "decimal" => self.parse_number_type(obj),
             "integer" => self.parse_integer_type(obj),
             "array" => self.parse_array_type(obj),
             "object" => self.parse_object_type(obj),
             "boolean" => self.parse_boolean_type(),
             "null" => self.parse_null_type(),
+            "bigint" | "int" | "long" => Err(Error::UnsupportedTypeAlias {
+                instance_type: Box::from(instance_type),
+                suggestion: "integer",
+            }),
+            "bool" => Err(Error::UnsupportedTypeAlias {
+                instance_type: Box::from(instance_type),
+                suggestion: "boolean",
+            }),
             _ => Err(Error::UnsupportedType(Box::from(instance_type))),
         }
     }
@@ -377,44 +943,76 @@ impl<'a> Parser<'a> {
         Ok(format_type.to_regex().to_string())
     }
 
+    /// Returns the value of a bound keyword, like `maxLength`, ensuring it's a non-negative integer.
+    fn get_bound(obj: &serde_json::Map<String, Value>, key: &'static str) -> Result<Option<u64>> {
+        match obj.get(key) {
+            None => Ok(None),
+            Some(value) => match value.as_u64() {
+                Some(bound) => Ok(Some(bound)),
+                None => Err(Error::InvalidBound {
+                    key,
+                    value: Box::new(value.clone()),
+                }),
+            },
+        }
+    }
+
     fn parse_string_type(&mut self, obj: &serde_json::Map<String, Value>) -> Result<String> {
-        if obj.contains_key("maxLength") || obj.contains_key("minLength") {
-            let max_items = obj.get("maxLength");
-            let min_items = obj.get("minLength");
+        let length_bounds = if obj.contains_key("maxLength") || obj.contains_key("minLength") {
+            let max_items = Self::get_bound(obj, "maxLength")?;
+            let min_items = Self::get_bound(obj, "minLength")?;
 
             match (min_items, max_items) {
-                (Some(min), Some(max)) if min.as_f64() > max.as_f64() => {
-                    return Err(Error::MaxBoundError)
-                }
+                (Some(min), Some(max)) if min > max => return Err(Error::MaxBoundError),
                 _ => {}
             }
 
-            let formatted_max = max_items
-                .and_then(Value::as_u64)
-                .map_or("".to_string(), |n| format!("{}", n));
-            let formatted_min = min_items
-                .and_then(Value::as_u64)
-                .map_or("0".to_string(), |n| format!("{}", n));
+            let formatted_max = max_items.map_or("".to_string(), |n| format!("{}", n));
+            let formatted_min = min_items.map_or("0".to_string(), |n| format!("{}", n));
+            Some((formatted_min, formatted_max))
+        } else {
+            None
+        };
 
-            Ok(format!(
-                r#""{}{{{},{}}}""#,
-                types::STRING_INNER,
-                formatted_min,
-                formatted_max,
-            ))
-        } else if let Some(pattern) = obj.get("pattern").and_then(Value::as_str) {
-            if pattern.starts_with('^') && pattern.ends_with('$') {
-                Ok(format!(r#"("{}")"#, &pattern[1..pattern.len() - 1]))
-            } else {
-                Ok(format!(r#"("{}")"#, pattern))
-            }
+        if let Some(pattern) = obj.get("pattern").and_then(Value::as_str) {
+            Self::validate_pattern(pattern)?;
+            // The whole string must match, so leading and trailing anchors are redundant,
+            // while the group keeps alternations, like `^a|b$`, within the quotes.
+            let pattern = pattern.strip_prefix('^').unwrap_or(pattern);
+            let pattern = match pattern.strip_suffix('$') {
+                Some(stripped) if !stripped.ends_with('\\') => stripped,
+                _ => pattern,
+            };
+            // Length bounds can only be combined with a pattern which is a single repeated
+            // atom, like `[a-z]+`, by replacing its quantifier. Anything else would need
+            // an intersection of both, so it's rejected rather than dropping either.
+            let pattern = match (&length_bounds, Self::repeated_atom(pattern)) {
+                (Some((min, max)), Some((atom, min_repeats))) => {
+                    let min = min
+                        .parse::<u64>()
+                        .map_or(min_repeats, |n| n.max(min_repeats));
+                    if max.parse::<u64>().is_ok_and(|max| max < min) {
+                        return Err(Error::UnsatisfiableLength(min_repeats));
+                    }
+                    format!("{atom}{{{min},{max}}}")
+                }
+                (Some(_), None) => {
+                    return Err(Error::UnsupportedPatternWithLength(Box::from(pattern)))
+                }
+                (None, _) => pattern.to_string(),
+            };
+            Ok(format!(r#"("(?:{})")"#, pattern))
+        } else if let Some((min, max)) = length_bounds {
+            Ok(format!(r#""{}{{{},{}}}""#, self.string_inner(), min, max))
         } else if let Some(format) = obj.get("format").and_then(Value::as_str) {
             match types::FormatType::from_str(format) {
                 Some(format_type) => Ok(format_type.to_regex().to_string()),
                 None => Err(Error::StringTypeUnsupportedFormat(Box::from(format))),
             }
+        } else if obj.get("contentEncoding").and_then(Value::as_str) == Some("base64") {
+            Ok(types::BYTE.to_string())
         } else {
-            Ok(types::JsonType::String.to_regex().to_string())
+            Ok(self.string().to_string())
         }
     }
 
@@ -474,6 +1072,16 @@ impl<'a> Parser<'a> {
                 r"((-)?(0|[1-9][0-9]{}))(\.[0-9]{})?([eE][+-][0-9]{})?",
                 integers_quantifier, fraction_quantifier, exponent_quantifier
             ))
+        } else if let Some((sign, max_digits)) = Self::range_constraints(obj) {
+            let integers_quantifier = match max_digits {
+                Some(max_digits) => format!("{{0,{}}}", max_digits - 1),
+                None => "*".to_string(),
+            };
+            Ok(format!(
+                r"({sign}(0|[1-9][0-9]{integers_quantifier}))(\.[0-9]+)?([eE][+-][0-9]+)?"
+            ))
+        } else if let Some(pattern) = self.number_pattern {
+            Ok(format!("(?:{})", pattern))
         } else {
             let format_type = types::JsonType::Number;
             Ok(format_type.to_regex().to_string())
@@ -496,20 +1104,67 @@ impl<'a> Parser<'a> {
             };
 
             Ok(format!(r"(-)?(0|[1-9][0-9]{})", quantifier))
+        } else if let Some((sign, max_digits)) = Self::range_constraints(obj) {
+            Ok(match max_digits {
+                Some(max_digits) => format!(r"{sign}(0|[1-9][0-9]{{0,{}}})", max_digits - 1),
+                None => format!(r"{sign}(0|[1-9][0-9]*)"),
+            })
+        } else if let Some(pattern) = self.integer_pattern {
+            Ok(format!("(?:{})", pattern))
         } else {
             let format_type = types::JsonType::Integer;
             Ok(format_type.to_regex().to_string())
         }
     }
 
+    /// Returns the sign pattern and the maximum number of digits in the integer part allowed
+    /// by `minimum` and `maximum` bounds, or `None` if there are no bounds.
+    ///
+    /// Ranges of decimals can't be matched exactly, so the bounds are over-approximated:
+    /// a non-negative `minimum` forbids the minus sign, a negative `maximum` requires it and
+    /// the magnitude of the bounds caps the digits of the integer part, i.e. `maximum` of 150
+    /// still allows 999. Exponents aren't restricted, so `maximum` of 100 allows `1e+1`, as
+    /// well as `9e+9`. Exclusive bounds are treated as inclusive ones.
+    fn range_constraints(
+        obj: &serde_json::Map<String, Value>,
+    ) -> Option<(&'static str, Option<usize>)> {
+        let bound = |keys: [&str; 2]| keys.iter().find_map(|key| obj.get(*key)?.as_f64());
+        let minimum = bound(["minimum", "exclusiveMinimum"]);
+        let maximum = bound(["maximum", "exclusiveMaximum"]);
+        if minimum.is_none() && maximum.is_none() {
+            return None;
+        }
+
+        // Formatting the float itself keeps all digits of bounds beyond `u64::MAX`.
+        let digits = |bound: f64| bound.abs().trunc().to_string().len();
+        Some(match (minimum, maximum) {
+            (Some(minimum), maximum) if minimum >= 0.0 => ("", maximum.map(digits)),
+            (minimum, Some(maximum)) if maximum < 0.0 => ("-", minimum.map(digits)),
+            (minimum, maximum) => (
+                "(-)?",
+                minimum
+                    .zip(maximum)
+                    .map(|(minimum, maximum)| digits(minimum).max(digits(maximum))),
+            ),
+        })
+    }
+
     fn parse_object_type(&mut self, obj: &serde_json::Map<String, Value>) -> Result<String> {
         let min_properties = obj.get("minProperties").and_then(|v| v.as_u64());
         let max_properties = obj.get("maxProperties").and_then(|v| v.as_u64());
 
-        let num_repeats = Self::get_num_items_pattern(min_properties, max_properties);
-
-        if num_repeats.is_none() {
-            return Ok(format!(r"\{{{}\}}", self.whitespace_pattern));
+        // No properties are allowed at all, as opposed to an unconstrained count, so only
+        // the empty object matches.
+        if max_properties == Some(0) {
+            if min_properties.unwrap_or(0) > 0 {
+                return Err(Error::UnsatisfiablePropertyCount);
+            }
+            return Ok(format!(r"\{{{}\}}", self.bracket_whitespace()));
+        }
+        if let (Some(min), Some(max)) = (min_properties, max_properties) {
+            if min > max {
+                return Err(Error::UnsatisfiablePropertyCount);
+            }
         }
 
         let allow_empty = if min_properties.unwrap_or(0) == 0 {
@@ -529,7 +1184,10 @@ impl<'a> Parser<'a> {
                     json!({"type": "null"}),
                 ];
 
-                let depth = obj.get("depth").and_then(|v| v.as_u64()).unwrap_or(2);
+                let depth = obj
+                    .get("depth")
+                    .and_then(Value::as_u64)
+                    .unwrap_or(self.unconstrained_depth);
                 if depth > 0 {
                     legal_types.push(json!({"type": "object", "depth": depth - 1}));
                     legal_types.push(json!({"type": "array", "depth": depth - 1}));
@@ -541,19 +1199,34 @@ impl<'a> Parser<'a> {
             Some(props) => self.to_regex(props)?,
         };
 
+        let key_pattern = match obj.get("propertyNames") {
+            Some(Value::Object(property_names)) => {
+                // Property names are always strings, so the type could be omitted.
+                let mut property_names = property_names.clone();
+                property_names
+                    .entry("type")
+                    .or_insert_with(|| Value::String("string".to_string()));
+                self.to_regex(&Value::Object(property_names))?
+            }
+            _ => self.string().to_string(),
+        };
         let key_value_pattern = format!(
-            "{}{1}:{1}{value_pattern}",
-            types::STRING,
+            "{key_pattern}{0}:{0}{value_pattern}",
             self.whitespace_pattern,
         );
         let key_value_successor_pattern =
-            format!("{0},{0}{key_value_pattern}", self.whitespace_pattern,);
-        let multiple_key_value_pattern =
-            format!("({key_value_pattern}({key_value_successor_pattern}){{0,}}){allow_empty}");
+            format!("{0},{0}{key_value_pattern}", self.separator_whitespace());
+        // The first pair is followed by one less successors than the count of properties.
+        let num_repeats = Self::get_num_items_pattern(min_properties, max_properties)
+            .unwrap_or_else(|| "{0,}".to_string());
+        let multiple_key_value_pattern = format!(
+            "({key_value_pattern}({key_value_successor_pattern}){num_repeats}){allow_empty}"
+        );
 
         let res = format!(
             r"\{{{0}{1}{0}\}}",
-            self.whitespace_pattern, multiple_key_value_pattern
+            self.bracket_whitespace(),
+            multiple_key_value_pattern
         );
 
         Ok(res)
@@ -567,7 +1240,7 @@ impl<'a> Parser<'a> {
         .unwrap_or_else(|| String::from(""));
 
         if num_repeats.is_empty() {
-            return Ok(format!(r"\[{0}\]", self.whitespace_pattern));
+            return Ok(format!(r"\[{0}\]", self.bracket_whitespace()));
         }
 
         let allow_empty = if obj.get("minItems").and_then(Value::as_u64).unwrap_or(0) == 0 {
@@ -579,8 +1252,12 @@ impl<'a> Parser<'a> {
         if let Some(items) = obj.get("items") {
             let items_regex = self.to_regex(items)?;
             Ok(format!(
-                r"\[{0}(({1})(,{0}({1})){2}){3}{0}\]",
-                self.whitespace_pattern, items_regex, num_repeats, allow_empty
+                r"\[{0}(({1})(,{4}({1})){2}){3}{0}\]",
+                self.bracket_whitespace(),
+                items_regex,
+                num_repeats,
+                allow_empty,
+                self.separator_whitespace()
             ))
         } else {
             // parse unconstrained object case
@@ -592,7 +1269,10 @@ impl<'a> Parser<'a> {
                 json!({"type": "string"}),
             ];
 
-            let depth = obj.get("depth").and_then(Value::as_u64).unwrap_or(2);
+            let depth = obj
+                .get("depth")
+                .and_then(Value::as_u64)
+                .unwrap_or(self.unconstrained_depth);
             if depth > 0 {
                 legal_types.push(json!({"type": "object", "depth": depth - 1}));
                 legal_types.push(json!({"type": "array", "depth": depth - 1}));
@@ -605,18 +1285,56 @@ impl<'a> Parser<'a> {
             let regexes_joined = regexes.join("|");
 
             Ok(format!(
-                r"\[{0}(({1})(,{0}({1})){2}){3}{0}\]",
-                self.whitespace_pattern, regexes_joined, num_repeats, allow_empty
+                r"\[{0}(({1})(,{4}({1})){2}){3}{0}\]",
+                self.bracket_whitespace(),
+                regexes_joined,
+                num_repeats,
+                allow_empty,
+                self.separator_whitespace()
             ))
         }
     }
 
+    /// Resolves a fragment of local reference, either JSON pointer, like `#/$defs/a`,
+    /// or a plain name, like `#a`, declared by `$anchor` or `$dynamicAnchor`.
+    fn resolve_fragment(&self, fragment: &str) -> Result<&'a Value> {
+        if fragment.is_empty() || fragment.starts_with('/') {
+            let path_parts: Vec<&str> = fragment.split('/').filter(|&s| !s.is_empty()).collect();
+            Self::resolve_local_ref(self.root, &path_parts)
+        } else {
+            Self::find_anchor(self.root, fragment)
+                .ok_or_else(|| Error::AnchorNotFound(Box::from(fragment)))
+        }
+    }
+
+    fn find_anchor<'b>(schema: &'b Value, anchor: &str) -> Option<&'b Value> {
+        match schema {
+            Value::Object(obj) => {
+                let is_anchor = ["$anchor", "$dynamicAnchor"]
+                    .iter()
+                    .any(|key| obj.get(*key).and_then(Value::as_str) == Some(anchor));
+                if is_anchor {
+                    return Some(schema);
+                }
+                obj.values()
+                    .find_map(|value| Self::find_anchor(value, anchor))
+            }
+            Value::Array(items) => items
+                .iter()
+                .find_map(|item| Self::find_anchor(item, anchor)),
+            _ => None,
+        }
+    }
+
     fn resolve_local_ref<'b>(schema: &'b Value, path_parts: &[&str]) -> Result<&'b Value> {
         let mut current = schema;
         for &part in path_parts {
-            current = current
-                .get(part)
-                .ok_or_else(|| Error::InvalidRefecencePath(Box::from(part)))?;
+            let next = match current {
+                // Numeric parts point to array elements, like `#/prefixItems/0`.
+                Value::Array(items) => part.parse::<usize>().ok().and_then(|i| items.get(i)),
+                _ => current.get(part),
+            };
+            current = next.ok_or_else(|| Error::InvalidRefecencePath(Box::from(part)))?;
         }
         Ok(current)
     }
@@ -638,7 +1356,27 @@ impl<'a> Parser<'a> {
         Ok((min_bound.flatten(), max_bound.flatten()))
     }
 
-    fn get_num_items_pattern(min_items: Option<u64>, max_items: Option<u64>) -> Option<String> {
+    /// Returns the atom and its minimum repetitions of a pattern consisting of a single atom
+    /// repeated with `+` or `*`, where atom is a simple character class, an escape sequence,
+    /// `.` or a literal char.
+    fn repeated_atom(pattern: &str) -> Option<(&str, u64)> {
+        let (atom, min_repeats) = match pattern.strip_suffix('+') {
+            Some(atom) => (atom, 1),
+            None => (pattern.strip_suffix('*')?, 0),
+        };
+        let is_class = atom.len() > 2
+            && atom.starts_with('[')
+            && atom.ends_with(']')
+            && !atom[1..atom.len() - 1].contains(['[', ']', '\\']);
+        let is_escape = atom.len() == 2 && atom.starts_with('\\');
+        let is_char = atom.chars().count() == 1 && !"()|?+*{}[]^$\\".contains(atom);
+        (is_class || is_escape || is_char).then_some((atom, min_repeats))
+    }
+
+    pub(crate) fn get_num_items_pattern(
+        min_items: Option<u64>,
+        max_items: Option<u64>,
+    ) -> Option<String> {
         let min_items = min_items.unwrap_or(0);
 
         match max_items {
@@ -657,3 +1395,37 @@ impl<'a> Parser<'a> {
         }
     }
 }
+
+/// Turns every capturing group of the regex into a non-capturing one.
+///
+/// Escaped parentheses and parentheses within character classes are literal and left as is,
+/// as well as groups with flags or names, like `(?i)` or `(?P<name>...)`.
+pub(crate) fn non_capturing_groups(regex: &str) -> String {
+    let mut result = String::with_capacity(regex.len());
+    let mut chars = regex.chars().peekable();
+    let mut class_depth = 0usize;
+    while let Some(c) = chars.next() {
+        result.push(c);
+        match c {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    result.push(escaped);
+                }
+            }
+            '[' => {
+                class_depth += 1;
+                // Negation and closing bracket right after the opening one are literal.
+                if let Some(caret) = chars.next_if_eq(&'^') {
+                    result.push(caret);
+                }
+                if let Some(bracket) = chars.next_if_eq(&']') {
+                    result.push(bracket);
+                }
+            }
+            ']' if class_depth > 0 => class_depth -= 1,
+            '(' if class_depth == 0 && chars.peek() != Some(&'?') => result.push_str("?:"),
+            _ => {}
+        }
+    }
+    result
+}
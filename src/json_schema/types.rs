@@ -4,10 +4,17 @@
 // allow `\"`, `\\`, or any character which isn't a control sequence
 pub static STRING_INNER: &str = r#"([^"\\\x00-\x1F\x7F-\x9F]|\\["\\/bfnrt])"#;
 pub static STRING: &str = r#""([^"\\\x00-\x1F\x7F-\x9F]|\\["\\/bfnrt])*""#;
+// allow `\"`, `\\`, or any other character, including control ones
+pub static STRING_INNER_WITH_CONTROL_CHARS: &str = r#"([^"\\]|\\["\\/bfnrt])"#;
+pub static STRING_WITH_CONTROL_CHARS: &str = r#""([^"\\]|\\["\\/bfnrt])*""#;
 pub static INTEGER: &str = r#"(-)?(0|[1-9][0-9]*)"#;
 pub static NUMBER: &str = r#"((-)?(0|[1-9][0-9]*))(\.[0-9]+)?([eE][+-][0-9]+)?"#;
+/// Numbers with a non-zero fractional part, written without exponent.
+pub static NON_INTEGER_NUMBER: &str = r#"((-)?(0|[1-9][0-9]*))(\.[0-9]*[1-9])"#;
 pub static BOOLEAN: &str = r#"(true|false)"#;
 pub static NULL: &str = r#"null"#;
+/// Empty character class, which never matches, used for the `false` schema.
+pub static NOTHING: &str = r#"[^\x00-\x{10FFFF}]"#;
 
 /// Default whitespace pattern used for generating a regular expression from JSON schema.
 ///
@@ -48,6 +55,12 @@ pub static UUID: &str = r#""[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9
 pub static URI: &str = r#""(?:(https?|ftp):\/\/([^\s:@]+(:[^\s:@]*)?@)?([a-zA-Z\d.-]+\.[a-zA-Z]{2,}|localhost)(:\d+)?(\/[^\s?#]*)?(\?[^\s#]*)?(#[^\s]*)?|urn:[a-zA-Z\d][a-zA-Z\d\-]{0,31}:[^\s]+)""#;
 // https://www.rfc-editor.org/rfc/rfc5322 and https://stackoverflow.com/questions/13992403/regex-validation-of-email-addresses-according-to-rfc5321-rfc5322
 pub static EMAIL: &str = r#""(?:[a-z0-9!#$%&'*+/=?^_`{|}~-]+(?:\.[a-z0-9!#$%&'*+/=?^_`{|}~-]+)*|"(?:[\x01-\x08\x0b\x0c\x0e-\x1f\x21\x23-\x5b\x5d-\x7f]|\\[\x01-\x09\x0b\x0c\x0e-\x7f])*")@(?:(?:[a-z0-9](?:[a-z0-9-]*[a-z0-9])?\.)+[a-z0-9](?:[a-z0-9-]*[a-z0-9])?|\[(?:(?:(2(5[0-5]|[0-4][0-9])|1[0-9][0-9]|[1-9]?[0-9]))\.){3}(?:(2(5[0-5]|[0-4][0-9])|1[0-9][0-9]|[1-9]?[0-9])|[a-z0-9-]*[a-z0-9]:(?:[\x01-\x08\x0b\x0c\x0e-\x1f\x21-\x5a\x53-\x7f]|\\[\x01-\x09\x0b\x0c\x0e-\x7f])+)\])""#;
+// https://datatracker.ietf.org/doc/html/rfc4648#section-4, padded base64 as OpenAPI `byte`
+pub static BYTE: &str = r#""(?:[A-Za-z0-9+/]{4})*(?:[A-Za-z0-9+/]{2}==|[A-Za-z0-9+/]{3}=)?""#;
+// OpenAPI `binary` is a sequence of octets without any encoding, so any string is allowed
+pub static BINARY: &str = r#""([^"\\\x00-\x1F\x7F-\x9F]|\\["\\/bfnrt])*""#;
+// https://www.w3.org/TR/css-color-4/#hex-notation, without 4-digit form
+pub static COLOR: &str = r##""#(?:[0-9a-fA-F]{3}|[0-9a-fA-F]{6}|[0-9a-fA-F]{8})""##;
 
 /// Supported format type of the `JsonType::String`.
 #[derive(Debug, PartialEq)]
@@ -58,6 +71,9 @@ pub enum FormatType {
     Uuid,
     Uri,
     Email,
+    Byte,
+    Binary,
+    Color,
 }
 
 impl FormatType {
@@ -69,6 +85,9 @@ impl FormatType {
             FormatType::Uuid => UUID,
             FormatType::Uri => URI,
             FormatType::Email => EMAIL,
+            FormatType::Byte => BYTE,
+            FormatType::Binary => BINARY,
+            FormatType::Color => COLOR,
         }
     }
 
@@ -81,6 +100,9 @@ impl FormatType {
             "uuid" => Some(FormatType::Uuid),
             "uri" => Some(FormatType::Uri),
             "email" => Some(FormatType::Email),
+            "byte" => Some(FormatType::Byte),
+            "binary" => Some(FormatType::Binary),
+            "color" => Some(FormatType::Color),
             _ => None,
         }
     }
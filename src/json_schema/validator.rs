@@ -0,0 +1,189 @@
+//! Incremental validation of raw text against a regular expression.
+
+use regex_automata::dfa::dense::DFA;
+use regex_automata::dfa::Automaton;
+use regex_automata::util::primitives::StateID as AutomataStateId;
+use regex_automata::{Anchored, MatchKind};
+
+use crate::{Error, Result};
+
+/// Builds a DFA reporting all matches, so a lower priority alternative, like `abc` of `a|abc`,
+/// isn't dropped once a higher priority one matches.
+pub(crate) fn build_dfa(regex: &str) -> Result<DFA<Vec<u32>>> {
+    let dfa = DFA::builder()
+        .configure(DFA::config().match_kind(MatchKind::All))
+        .build(regex)
+        .map_err(Box::new)?;
+    Ok(dfa)
+}
+
+/// Checks if no bytes could follow the state to complete a match.
+///
+/// Matches are delayed by one byte, so a match state, which doesn't match at the end of input,
+/// is reached by a byte after a match, like `.` in `1.5`, and it's only rejecting if no byte
+/// continues from it.
+pub(crate) fn is_rejecting_state(dfa: &DFA<Vec<u32>>, state: AutomataStateId) -> bool {
+    if dfa.is_dead_state(state) || dfa.is_quit_state(state) {
+        return true;
+    }
+    dfa.is_match_state(state)
+        && !dfa.is_match_state(dfa.next_eoi_state(state))
+        && (0..=u8::MAX).all(|byte| dfa.is_dead_state(dfa.next_state(state, byte)))
+}
+
+/// Outcome of feeding bytes into a [`Validator`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeedResult {
+    /// Bytes consumed so far fully match the regular expression.
+    Accepting,
+    /// Bytes consumed so far can never be completed into a match.
+    Rejected,
+    /// Bytes consumed so far are a valid prefix, but not yet a full match.
+    Incomplete,
+}
+
+/// `Validator` consumes text byte by byte and tracks the state of the regex DFA.
+///
+/// ## Example
+/// ```rust
+/// # use outlines_core::Error;
+/// use outlines_core::json_schema::{FeedResult, Validator};
+///
+/// # fn main() -> Result<(), Error> {
+/// let mut validator = Validator::new("[0-9]+")?;
+/// assert_eq!(validator.feed(b"12"), FeedResult::Accepting);
+/// assert_eq!(validator.feed(b"a"), FeedResult::Rejected);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Validator {
+    dfa: DFA<Vec<u32>>,
+    start_state: AutomataStateId,
+    state: AutomataStateId,
+    rejected: bool,
+}
+
+impl Validator {
+    /// Builds a `Validator` from a regular expression.
+    pub fn new(regex: &str) -> Result<Self> {
+        let dfa = build_dfa(regex)?;
+        let start_state = match dfa.universal_start_state(Anchored::Yes) {
+            Some(s) => s,
+            None => return Err(Error::DfaHasNoStartState),
+        };
+        Ok(Self {
+            dfa,
+            start_state,
+            state: start_state,
+            rejected: false,
+        })
+    }
+
+    /// Consumes the next chunk of bytes and reports whether the text seen so far matches.
+    ///
+    /// Once rejected, the validator stays rejected until [`Validator::reset`] is called.
+    pub fn feed(&mut self, bytes: &[u8]) -> FeedResult {
+        if self.rejected {
+            return FeedResult::Rejected;
+        }
+        for byte in bytes {
+            self.state = self.dfa.next_state(self.state, *byte);
+            if is_rejecting_state(&self.dfa, self.state) {
+                self.rejected = true;
+                return FeedResult::Rejected;
+            }
+        }
+        self.status()
+    }
+
+    /// Reports the current status without consuming any bytes.
+    pub fn status(&self) -> FeedResult {
+        if self.rejected {
+            FeedResult::Rejected
+        } else if self.dfa.is_match_state(self.dfa.next_eoi_state(self.state)) {
+            FeedResult::Accepting
+        } else {
+            FeedResult::Incomplete
+        }
+    }
+
+    /// Moves the validator back to the initial state.
+    pub fn reset(&mut self) {
+        self.state = self.start_state;
+        self.rejected = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json_schema::regex_from_str;
+
+    #[test]
+    fn feed_valid_object_in_chunks() {
+        let schema = r#"{
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "age": { "type": "integer" }
+            },
+            "required": ["name", "age"]
+        }"#;
+        let regex = regex_from_str(schema, None, None).expect("To regex failed");
+        let mut validator = Validator::new(&regex).expect("Validator failed");
+
+        assert_eq!(validator.status(), FeedResult::Incomplete);
+        for chunk in [r#"{"na"#, r#"me": "Jo"#, r#"hn", "a"#, r#"ge": 4"#] {
+            assert_eq!(validator.feed(chunk.as_bytes()), FeedResult::Incomplete);
+        }
+        assert_eq!(validator.feed(b"2}"), FeedResult::Accepting);
+
+        validator.reset();
+        assert_eq!(validator.status(), FeedResult::Incomplete);
+    }
+
+    #[test]
+    fn feed_malformed_object() {
+        let schema = r#"{
+            "type": "object",
+            "properties": { "age": { "type": "integer" } },
+            "required": ["age"]
+        }"#;
+        let regex = regex_from_str(schema, None, None).expect("To regex failed");
+        let mut validator = Validator::new(&regex).expect("Validator failed");
+
+        assert_eq!(validator.feed(br#"{"age": "#), FeedResult::Incomplete);
+        assert_eq!(validator.feed(br#""42"}"#), FeedResult::Rejected);
+        // Stays rejected even if valid bytes follow.
+        assert_eq!(validator.feed(b"42}"), FeedResult::Rejected);
+
+        validator.reset();
+        assert_eq!(validator.feed(br#"{"age": 42}"#), FeedResult::Accepting);
+    }
+
+    #[test]
+    fn feed_decimal_numbers() {
+        let regex = regex_from_str(r#"{"type": "number"}"#, None, None).expect("To regex failed");
+        let mut validator = Validator::new(&regex).expect("Validator failed");
+        assert_eq!(validator.feed(b"1.5"), FeedResult::Accepting);
+
+        validator.reset();
+        assert_eq!(validator.feed(b"1"), FeedResult::Accepting);
+        assert_eq!(validator.feed(b"."), FeedResult::Incomplete);
+        assert_eq!(validator.feed(b"5e-3"), FeedResult::Accepting);
+        assert_eq!(validator.feed(b"x"), FeedResult::Rejected);
+    }
+
+    #[test]
+    fn feed_lower_priority_alternative() {
+        let mut validator = Validator::new("a|abc").expect("Validator failed");
+        assert_eq!(validator.feed(b"a"), FeedResult::Accepting);
+        assert_eq!(validator.feed(b"b"), FeedResult::Incomplete);
+        assert_eq!(validator.feed(b"c"), FeedResult::Accepting);
+        assert_eq!(validator.feed(b"c"), FeedResult::Rejected);
+
+        validator.reset();
+        assert_eq!(validator.feed(b"ax"), FeedResult::Rejected);
+    }
+}
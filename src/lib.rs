@@ -16,6 +16,8 @@
 //!
 //! Whitespace pattern could be customized, otherwise the default [`json_schema::WHITESPACE`] pattern is used.
 //!
+//! Schema to [`index::Index`] construction could be done in one call with [`index::Index::new_from_schema`].
+//!
 //! Note, that not all the features of JSON schema are supported for regex generation: [Supported Features](json_schema#supported-features)
 //!
 //! ## `Index`
@@ -81,13 +83,17 @@
 //! ```
 
 pub mod error;
+mod fingerprint;
+pub mod guide;
 pub mod index;
 pub mod json_schema;
 pub mod prelude;
 pub mod primitives;
+pub mod serialization;
 pub mod vocabulary;
 
-pub use error::{Error, Result};
+pub use error::{Error, ErrorKind, Result};
+pub use json_schema::build_index_from_schema;
 
 #[cfg(feature = "python-bindings")]
 mod python_bindings;
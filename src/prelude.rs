@@ -5,5 +5,6 @@ pub use tokenizers::FromPretrainedParameters;
 
 pub use super::index::Index;
 pub use super::json_schema;
+pub use super::json_schema::build_index_from_schema;
 pub use super::primitives::{StateId, Token, TokenId};
 pub use super::vocabulary::Vocabulary;
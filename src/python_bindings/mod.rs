@@ -1,20 +1,24 @@
 //! Provides tools and interfaces to integrate the crate's functionality with Python.
 
 use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
 use std::sync::Arc;
 
-use bincode::{config, Decode, Encode};
+use bincode::{Decode, Encode};
 use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
-use pyo3::types::{PyAny, PyDict};
+use pyo3::types::{PyAny, PyBytes, PyDict};
 use pyo3::wrap_pyfunction;
 use rustc_hash::{FxHashMap as HashMap, FxHashSet as HashSet};
 #[cfg(feature = "hugginface-hub")]
 use tokenizers::FromPretrainedParameters;
 
-use crate::index::Index;
+use crate::guide::MultiGuide;
+use crate::index::{Index, RegexFlags};
 use crate::json_schema;
 use crate::prelude::*;
+use crate::serialization::{self, DecodeContext};
 
 macro_rules! type_name {
     ($obj:expr) => {
@@ -26,6 +30,7 @@ macro_rules! type_name {
 /// Guide object based on Index.
 #[pyclass(name = "Guide", module = "outlines_core")]
 #[derive(Clone, Debug, PartialEq, Encode, Decode)]
+#[bincode(decode_context = "DecodeContext")]
 pub struct PyGuide {
     state: StateId,
     index: PyIndex,
@@ -67,6 +72,12 @@ impl PyGuide {
         self.state_cache.len()
     }
 
+    /// Get the maximum number of rollback steps, which could ever be available.
+    /// `get_allowed_rollback` grows with each advance up to this value and shrinks with rollbacks.
+    fn get_max_rollback(&self) -> usize {
+        self.state_cache.capacity()
+    }
+
     /// Guide moves to the next state provided by the token id and returns a list of allowed tokens, unless return_tokens is False.
     #[pyo3(signature = (token_id, return_tokens=None))]
     fn advance(
@@ -171,6 +182,7 @@ impl PyGuide {
                 )
             ));
         }
+        self.validate_mask_token_ids(numel)?;
         unsafe {
             std::ptr::write_bytes(data_ptr as *mut u8, 0, numel * 4);
         }
@@ -214,36 +226,153 @@ impl PyGuide {
     fn __reduce__(&self) -> PyResult<(PyObject, (Vec<u8>,))> {
         Python::with_gil(|py| {
             let cls = PyModule::import(py, "outlines_core")?.getattr("Guide")?;
-            let binary_data: Vec<u8> =
-                bincode::encode_to_vec(self, config::standard()).map_err(|e| {
-                    PyErr::new::<PyValueError, _>(format!("Serialization of Guide failed: {}", e))
-                })?;
+            let binary_data: Vec<u8> = serialization::encode_to_vec(self).map_err(|e| {
+                PyErr::new::<PyValueError, _>(format!("Serialization of Guide failed: {}", e))
+            })?;
             Ok((cls.getattr("from_binary")?.unbind(), (binary_data,)))
         })
     }
 
     #[staticmethod]
     fn from_binary(binary_data: Vec<u8>) -> PyResult<Self> {
-        let (guide, _): (PyGuide, usize) =
-            bincode::decode_from_slice(&binary_data[..], config::standard()).map_err(|e| {
+        serialization::decode_from_slice(&binary_data[..]).map_err(|e| {
+            PyErr::new::<PyValueError, _>(format!("Deserialization of Guide failed: {}", e))
+        })
+    }
+
+    /// Serializes the state of the Guide without its Index, so many guides could share
+    /// a single Index, restored with `from_binary_with_index`.
+    fn to_binary_without_index(&self, py: Python<'_>) -> PyResult<Py<PyBytes>> {
+        let state = (
+            self.state,
+            self.state_cache.capacity(),
+            self.state_cache.clone(),
+        );
+        let binary_data = serialization::encode_to_vec(&state).map_err(|e| {
+            PyErr::new::<PyValueError, _>(format!("Serialization of Guide failed: {}", e))
+        })?;
+        Ok(PyBytes::new(py, &binary_data).unbind())
+    }
+
+    /// Restores the Guide serialized by `to_binary_without_index` against the given Index,
+    /// which must be the Index the Guide was created with.
+    #[staticmethod]
+    fn from_binary_with_index(binary_data: Vec<u8>, index: PyIndex) -> PyResult<Self> {
+        let (state, max_rollback, cached_states): (StateId, usize, VecDeque<StateId>) =
+            serialization::decode_from_slice(&binary_data[..]).map_err(|e| {
                 PyErr::new::<PyValueError, _>(format!("Deserialization of Guide failed: {}", e))
             })?;
-        Ok(guide)
+        if let Some(unknown) = std::iter::once(&state)
+            .chain(cached_states.iter())
+            .find(|state| !index.0.contains_state(state))
+        {
+            return Err(PyValueError::new_err(format!(
+                "State {unknown} of the Guide doesn't belong to the Index"
+            )));
+        }
+        let mut state_cache = VecDeque::with_capacity(max_rollback);
+        state_cache.extend(cached_states);
+        Ok(PyGuide {
+            state,
+            index,
+            state_cache,
+        })
+    }
+
+    /// Saves the Guide into a file, in the same binary format as pickling uses.
+    fn save(&self, path: &str) -> PyResult<()> {
+        save_to_file(self, path, "Guide")
+    }
+
+    /// Loads the Guide previously saved into a file.
+    #[staticmethod]
+    fn load(path: &str) -> PyResult<Self> {
+        load_from_file(path, "Guide")
+    }
+}
+
+impl PyGuide {
+    /// Fails if a token allowed in the current state doesn't fit into the mask of `numel`
+    /// 32-bit elements, instead of leaving it silently missing from the mask.
+    fn validate_mask_token_ids(&self, numel: usize) -> PyResult<()> {
+        let bound = numel * 32;
+        let out_of_range = self
+            .index
+            .0
+            .allowed_tokens_iter(&self.state)
+            .into_iter()
+            .flatten()
+            .filter(|token_id| **token_id as usize >= bound)
+            .max();
+        match out_of_range {
+            Some(id) => Err(PyValueError::new_err(format!(
+                "Invalid buffer size: allowed token id {} doesn't fit into {} elements.",
+                id, numel
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// MultiGuide object, satisfying several Index objects at once.
+#[pyclass(name = "MultiGuide", module = "outlines_core")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PyMultiGuide(MultiGuide);
+
+#[pymethods]
+impl PyMultiGuide {
+    /// Creates a MultiGuide object at the initial state of every Index.
+    #[new]
+    fn __new__(indexes: Vec<PyIndex>) -> PyResult<Self> {
+        let indexes = indexes.into_iter().map(|index| index.0).collect();
+        Ok(PyMultiGuide(MultiGuide::new(indexes)?))
+    }
+
+    /// Retrieves current state ids of every Index.
+    fn get_states(&self) -> Vec<StateId> {
+        self.0.states().to_vec()
+    }
+
+    /// Gets the list of tokens allowed by all indexes.
+    fn get_tokens(&self) -> Vec<TokenId> {
+        self.0.allowed_tokens()
+    }
+
+    /// Advances every Index by the token id and returns a list of allowed tokens.
+    fn advance(&mut self, token_id: TokenId) -> PyResult<Vec<TokenId>> {
+        Ok(self.0.advance(token_id)?)
+    }
+
+    /// Checks if every Index is in a final state.
+    fn is_finished(&self) -> bool {
+        self.0.is_finished()
     }
 }
 
 /// Index object based on regex and vocabulary.
 #[pyclass(name = "Index", module = "outlines_core")]
 #[derive(Clone, Debug, PartialEq, Encode, Decode)]
+#[bincode(decode_context = "DecodeContext")]
 pub struct PyIndex(Arc<Index>);
 
 #[pymethods]
 impl PyIndex {
     /// Creates an index from a regex and vocabulary.
     #[new]
-    fn __new__(py: Python<'_>, regex: &str, vocabulary: &PyVocabulary) -> PyResult<Self> {
+    #[pyo3(signature = (regex, vocabulary, case_insensitive=false, dot_matches_new_line=false))]
+    fn __new__(
+        py: Python<'_>,
+        regex: &str,
+        vocabulary: &PyVocabulary,
+        case_insensitive: bool,
+        dot_matches_new_line: bool,
+    ) -> PyResult<Self> {
+        let flags = RegexFlags {
+            case_insensitive,
+            dot_matches_new_line,
+        };
         py.allow_threads(|| {
-            Index::new(regex, &vocabulary.0)
+            Index::new_with_flags(regex, &vocabulary.0, flags)
                 .map(|x| PyIndex(Arc::new(x)))
                 .map_err(Into::into)
         })
@@ -264,6 +393,21 @@ impl PyIndex {
         self.0.is_final_state(&state)
     }
 
+    /// Determines whether generation may stop at the state.
+    fn can_terminate(&self, state: StateId) -> bool {
+        self.0.can_terminate(&state)
+    }
+
+    /// Determines whether generation must stop at the state, since only EOS token is allowed.
+    fn must_terminate(&self, state: StateId) -> bool {
+        self.0.must_terminate(&state)
+    }
+
+    /// Get allowed tokens from the state, which lead into a final state.
+    fn get_terminating_tokens(&self, state: StateId) -> Vec<TokenId> {
+        self.0.terminating_tokens(&state)
+    }
+
     /// Get all final states.
     fn get_final_states(&self) -> HashSet<StateId> {
         self.0.final_states().clone()
@@ -274,11 +418,35 @@ impl PyIndex {
         self.0.transitions().clone()
     }
 
+    /// Returns outgoing transitions of a single state.
+    fn get_transitions_for_state(&self, state: StateId) -> Option<HashMap<TokenId, StateId>> {
+        self.0.transitions().get(&state).cloned()
+    }
+
+    /// Returns a lazy iterator over `(state, token_id, next_state)` transition triples.
+    fn iter_transitions(&self) -> PyTransitionsIterator {
+        PyTransitionsIterator {
+            states: self.0.transitions().keys().copied().collect(),
+            row: Vec::new(),
+            index: Arc::clone(&self.0),
+        }
+    }
+
     /// Returns the ID of the initial state of the index.
     fn get_initial_state(&self) -> StateId {
         self.0.initial_state()
     }
 
+    /// Returns the number of distinct states of the index.
+    fn num_states(&self) -> usize {
+        self.0.num_states()
+    }
+
+    /// Returns the number of transitions of the index.
+    fn num_transitions(&self) -> usize {
+        self.0.num_transitions()
+    }
+
     /// Gets the debug string representation of the index.
     fn __repr__(&self) -> String {
         format!("{:#?}", self.0)
@@ -302,28 +470,69 @@ impl PyIndex {
     fn __reduce__(&self) -> PyResult<(PyObject, (Vec<u8>,))> {
         Python::with_gil(|py| {
             let cls = PyModule::import(py, "outlines_core")?.getattr("Index")?;
-            let binary_data: Vec<u8> = bincode::encode_to_vec(&self.0, config::standard())
-                .map_err(|e| {
-                    PyErr::new::<PyValueError, _>(format!("Serialization of Index failed: {}", e))
-                })?;
+            let binary_data: Vec<u8> = serialization::encode_to_vec(&self.0).map_err(|e| {
+                PyErr::new::<PyValueError, _>(format!("Serialization of Index failed: {}", e))
+            })?;
             Ok((cls.getattr("from_binary")?.unbind(), (binary_data,)))
         })
     }
 
     #[staticmethod]
     fn from_binary(binary_data: Vec<u8>) -> PyResult<Self> {
-        let (index, _): (Index, usize) =
-            bincode::decode_from_slice(&binary_data[..], config::standard()).map_err(|e| {
-                PyErr::new::<PyValueError, _>(format!("Deserialization of Index failed: {}", e))
-            })?;
+        let index: Index = serialization::decode_from_slice(&binary_data[..]).map_err(|e| {
+            PyErr::new::<PyValueError, _>(format!("Deserialization of Index failed: {}", e))
+        })?;
         Ok(PyIndex(Arc::new(index)))
     }
+
+    /// Saves the Index into a file, in the same binary format as pickling uses.
+    fn save(&self, path: &str) -> PyResult<()> {
+        save_to_file(&*self.0, path, "Index")
+    }
+
+    /// Loads the Index previously saved into a file.
+    #[staticmethod]
+    fn load(path: &str) -> PyResult<Self> {
+        let index: Index = load_from_file(path, "Index")?;
+        Ok(PyIndex(Arc::new(index)))
+    }
+}
+
+/// Iterator over transitions of Index, which doesn't clone the whole transitions map.
+#[pyclass(name = "TransitionsIterator", module = "outlines_core")]
+pub struct PyTransitionsIterator {
+    index: Arc<Index>,
+    states: Vec<StateId>,
+    row: Vec<(StateId, TokenId, StateId)>,
+}
+
+#[pymethods]
+impl PyTransitionsIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self) -> Option<(StateId, TokenId, StateId)> {
+        // Transitions are materialized one state at a time.
+        while self.row.is_empty() {
+            let state = self.states.pop()?;
+            if let Some(map) = self.index.transitions().get(&state) {
+                self.row = map
+                    .iter()
+                    .map(|(token_id, next_state)| (state, *token_id, *next_state))
+                    .collect();
+            }
+        }
+        self.row.pop()
+    }
 }
 
 /// LLM vocabulary.
 #[pyclass(name = "Vocabulary", module = "outlines_core")]
 #[derive(Clone, Debug, Encode, Decode)]
-pub struct PyVocabulary(Vocabulary);
+#[bincode(decode_context = "DecodeContext")]
+/// Vocabulary is shared with iterators over it and copied on write, if modified meanwhile.
+pub struct PyVocabulary(Arc<Vocabulary>);
 
 #[pymethods]
 impl PyVocabulary {
@@ -331,10 +540,16 @@ impl PyVocabulary {
     #[new]
     fn __new__(py: Python<'_>, eos_token_id: TokenId, map: Py<PyAny>) -> PyResult<PyVocabulary> {
         if let Ok(dict) = map.extract::<HashMap<String, Vec<TokenId>>>(py) {
-            return Ok(PyVocabulary(Vocabulary::try_from((eos_token_id, dict))?));
+            return Ok(PyVocabulary(Arc::new(Vocabulary::try_from((
+                eos_token_id,
+                dict,
+            ))?)));
         }
         if let Ok(dict) = map.extract::<HashMap<Vec<u8>, Vec<TokenId>>>(py) {
-            return Ok(PyVocabulary(Vocabulary::try_from((eos_token_id, dict))?));
+            return Ok(PyVocabulary(Arc::new(Vocabulary::try_from((
+                eos_token_id,
+                dict,
+            ))?)));
         }
 
         let message = "Expected a dict with keys of type str or bytes and values of type list[int]";
@@ -350,6 +565,30 @@ impl PyVocabulary {
         }
     }
 
+    /// Creates a vocabulary from eos token id and a map of string tokens to token ids.
+    #[staticmethod]
+    fn from_dict_str(
+        eos_token_id: TokenId,
+        map: HashMap<String, Vec<TokenId>>,
+    ) -> PyResult<PyVocabulary> {
+        Ok(PyVocabulary(Arc::new(Vocabulary::try_from((
+            eos_token_id,
+            map,
+        ))?)))
+    }
+
+    /// Creates a vocabulary from eos token id and a map of bytes tokens to token ids.
+    #[staticmethod]
+    fn from_dict_bytes(
+        eos_token_id: TokenId,
+        map: HashMap<Vec<u8>, Vec<TokenId>>,
+    ) -> PyResult<PyVocabulary> {
+        Ok(PyVocabulary(Arc::new(Vocabulary::try_from((
+            eos_token_id,
+            map,
+        ))?)))
+    }
+
     /// Creates the vocabulary of a pre-trained model.
     #[staticmethod]
     #[pyo3(signature = (model, revision=None, token=None))]
@@ -367,16 +606,16 @@ impl PyVocabulary {
             params.token = token
         }
         let v = Vocabulary::from_pretrained(model.as_str(), Some(params))?;
-        Ok(PyVocabulary(v))
+        Ok(PyVocabulary(Arc::new(v)))
     }
 
     /// Inserts new token with token_id or extends list of token_ids if token already present.
     fn insert(&mut self, py: Python<'_>, token: Py<PyAny>, token_id: TokenId) -> PyResult<()> {
         if let Ok(t) = token.extract::<String>(py) {
-            return Ok(self.0.try_insert(t, token_id)?);
+            return Ok(Arc::make_mut(&mut self.0).try_insert(t, token_id)?);
         }
         if let Ok(t) = token.extract::<Token>(py) {
-            return Ok(self.0.try_insert(t, token_id)?);
+            return Ok(Arc::make_mut(&mut self.0).try_insert(t, token_id)?);
         }
         Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!(
             "Expected a token of type str or bytes, got {:?}",
@@ -387,11 +626,11 @@ impl PyVocabulary {
     /// Removes a token from vocabulary.
     fn remove(&mut self, py: Python<'_>, token: Py<PyAny>) -> PyResult<()> {
         if let Ok(t) = token.extract::<String>(py) {
-            self.0.remove(t);
+            Arc::make_mut(&mut self.0).remove(t);
             return Ok(());
         }
         if let Ok(t) = token.extract::<Token>(py) {
-            self.0.remove(t);
+            Arc::make_mut(&mut self.0).remove(t);
             return Ok(());
         }
         Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!(
@@ -414,6 +653,16 @@ impl PyVocabulary {
         )))
     }
 
+    /// Returns an iterator over `(token, token_ids)` pairs, with tokens as bytes.
+    fn items(&self) -> PyVocabularyIterator {
+        PyVocabularyIterator::new(Arc::clone(&self.0), false)
+    }
+
+    /// Returns an iterator over tokens as bytes.
+    fn keys(&self) -> PyVocabularyIterator {
+        PyVocabularyIterator::new(Arc::clone(&self.0), true)
+    }
+
     /// Gets the end of sentence token id.
     fn get_eos_token_id(&self) -> TokenId {
         self.0.eos_token_id()
@@ -447,28 +696,170 @@ impl PyVocabulary {
     fn __reduce__(&self) -> PyResult<(PyObject, (Vec<u8>,))> {
         Python::with_gil(|py| {
             let cls = PyModule::import(py, "outlines_core")?.getattr("Vocabulary")?;
-            let binary_data: Vec<u8> =
-                bincode::encode_to_vec(self, config::standard()).map_err(|e| {
-                    PyErr::new::<PyValueError, _>(format!(
-                        "Serialization of Vocabulary failed: {}",
-                        e
-                    ))
-                })?;
+            let binary_data: Vec<u8> = serialization::encode_to_vec(self).map_err(|e| {
+                PyErr::new::<PyValueError, _>(format!("Serialization of Vocabulary failed: {}", e))
+            })?;
             Ok((cls.getattr("from_binary")?.unbind(), (binary_data,)))
         })
     }
 
     #[staticmethod]
     fn from_binary(binary_data: Vec<u8>) -> PyResult<Self> {
-        let (guide, _): (PyVocabulary, usize) =
-            bincode::decode_from_slice(&binary_data[..], config::standard()).map_err(|e| {
-                PyErr::new::<PyValueError, _>(format!(
-                    "Deserialization of Vocabulary failed: {}",
-                    e
-                ))
-            })?;
-        Ok(guide)
+        serialization::decode_from_slice(&binary_data[..]).map_err(|e| {
+            PyErr::new::<PyValueError, _>(format!("Deserialization of Vocabulary failed: {}", e))
+        })
     }
+
+    /// Saves the Vocabulary into a file, in the same binary format as pickling uses.
+    fn save(&self, path: &str) -> PyResult<()> {
+        save_to_file(self, path, "Vocabulary")
+    }
+
+    /// Loads the Vocabulary previously saved into a file.
+    #[staticmethod]
+    fn load(path: &str) -> PyResult<Self> {
+        load_from_file(path, "Vocabulary")
+    }
+}
+
+fn save_to_file<T: Encode>(value: &T, path: &str, name: &str) -> PyResult<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serialization::encode_into_writer(value, &mut writer).map_err(|e| {
+        PyErr::new::<PyValueError, _>(format!("Serialization of {} failed: {}", name, e))
+    })?;
+    Ok(writer.flush()?)
+}
+
+fn load_from_file<T: Decode<DecodeContext>>(path: &str, name: &str) -> PyResult<T> {
+    let reader = BufReader::new(File::open(path)?);
+    serialization::decode_from_reader(reader).map_err(|e| {
+        PyErr::new::<PyValueError, _>(format!("Deserialization of {} failed: {}", name, e))
+    })
+}
+
+/// Iterator over a snapshot of Vocabulary's tokens, which creates Python objects lazily.
+#[pyclass(name = "VocabularyIterator", module = "outlines_core")]
+pub struct PyVocabularyIterator {
+    /// Tokens of the snapshot, which aren't returned yet.
+    tokens: std::vec::IntoIter<Token>,
+    /// Snapshot shared with the vocabulary, unless it was modified since.
+    vocabulary: Arc<Vocabulary>,
+    keys_only: bool,
+}
+
+impl PyVocabularyIterator {
+    fn new(vocabulary: Arc<Vocabulary>, keys_only: bool) -> Self {
+        Self {
+            tokens: vocabulary
+                .tokens()
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>()
+                .into_iter(),
+            vocabulary,
+            keys_only,
+        }
+    }
+}
+
+#[pymethods]
+impl PyVocabularyIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        let Some(token) = self.tokens.next() else {
+            return Ok(None);
+        };
+        let item = if self.keys_only {
+            PyBytes::new(py, &token).into_any().unbind()
+        } else {
+            let token_ids = &self.vocabulary.tokens()[&token];
+            (PyBytes::new(py, &token), token_ids)
+                .into_pyobject(py)?
+                .into_any()
+                .unbind()
+        };
+        Ok(Some(item))
+    }
+
+    fn __len__(&self) -> usize {
+        self.tokens.len()
+    }
+}
+
+/// Builds indexes for many vocabularies from one regex, concurrently and without holding the GIL.
+#[pyfunction(name = "build_indexes")]
+pub fn build_indexes_py(
+    py: Python<'_>,
+    regex: &str,
+    vocabularies: Vec<PyRef<'_, PyVocabulary>>,
+) -> PyResult<Vec<PyIndex>> {
+    let vocabularies: Vec<&Vocabulary> = vocabularies.iter().map(|v| v.0.as_ref()).collect();
+    py.allow_threads(|| {
+        Index::new_many(regex, &vocabularies)
+            .map(|indexes| {
+                indexes
+                    .into_iter()
+                    .map(|index| PyIndex(Arc::new(index)))
+                    .collect()
+            })
+            .map_err(Into::into)
+    })
+}
+
+/// Writes masks of allowed tokens of many guides into rows of a contiguous 2D buffer,
+/// specified by `data_ptr`, with `rows` rows of `cols_per_row` elements each.
+/// `element_size` must be 4 and there must be one row per guide.
+///
+/// Row `i` gets the same mask as `guides[i].write_mask_into` would write.
+#[pyfunction(name = "write_masks_into")]
+pub fn write_masks_into_py(
+    guides: Vec<PyRef<'_, PyGuide>>,
+    data_ptr: usize,
+    rows: usize,
+    cols_per_row: usize,
+    element_size: usize,
+) -> PyResult<()> {
+    if rows != guides.len() {
+        return Err(PyValueError::new_err(format!(
+            "Invalid number of rows: got {} rows for {} guides.",
+            rows,
+            guides.len()
+        )));
+    }
+    if element_size != 4 {
+        return Err(PyValueError::new_err(format!(
+            "Invalid element size: got {} bytes per element, expected 4 bytes (32-bit integer).",
+            element_size
+        )));
+    }
+    // Check every row fits before writing any, so the buffer isn't left partially written.
+    if let Some(expected_elements) = guides
+        .iter()
+        .map(|guide| guide.index.0.vocab_size().div_ceil(32))
+        .max()
+    {
+        if cols_per_row < expected_elements {
+            return Err(PyValueError::new_err(format!(
+                "Invalid row size: got {} elements per row, expected {} elements. \
+                Ensure that the mask tensor has shape ({}, (vocab_size + 31) // 32) and uses 32-bit integers.",
+                cols_per_row, expected_elements, rows
+            )));
+        }
+    }
+    for guide in guides.iter() {
+        guide.validate_mask_token_ids(cols_per_row)?;
+    }
+    for (row, guide) in guides.iter().enumerate() {
+        guide.write_mask_into(
+            data_ptr + row * cols_per_row * element_size,
+            cols_per_row,
+            element_size,
+        )?;
+    }
+    Ok(())
 }
 
 /// Creates regex string from JSON schema with optional whitespace pattern.
@@ -486,6 +877,44 @@ pub fn build_regex_from_schema_py(
         .map_err(|e| PyValueError::new_err(e.to_string()))
 }
 
+pyo3::create_exception!(
+    outlines_core,
+    JsonSchemaError,
+    PyValueError,
+    "Failure of regex generation, with JSON `pointer` to the failing sub-schema and `reason`."
+);
+
+/// Creates regex string from JSON schema with optional whitespace pattern, on failure
+/// raises `JsonSchemaError` pointing to the failing part of the schema.
+#[pyfunction(name = "build_regex_from_schema_verbose")]
+#[pyo3(signature = (json_schema, whitespace_pattern=None, max_recursion_depth=3))]
+pub fn build_regex_from_schema_verbose_py(
+    py: Python<'_>,
+    json_schema: String,
+    whitespace_pattern: Option<&str>,
+    max_recursion_depth: usize,
+) -> PyResult<String> {
+    let value = serde_json::from_str(&json_schema).map_err(|_| {
+        PyErr::new::<pyo3::exceptions::PyTypeError, _>("Expected a valid JSON string.")
+    })?;
+    let options = json_schema::GenerationOptions {
+        whitespace_pattern: whitespace_pattern.map(str::to_string),
+        max_recursion_depth: Some(max_recursion_depth),
+        ..json_schema::GenerationOptions::default()
+    };
+    match json_schema::regex_from_value_verbose(&value, &options) {
+        Ok(regex) => Ok(regex),
+        Err(crate::Error::JsonSchemaAt { pointer, source }) => {
+            let err = JsonSchemaError::new_err(format!("{} at '{}'", source, pointer));
+            let err_value = err.value(py);
+            err_value.setattr("pointer", &*pointer)?;
+            err_value.setattr("reason", source.to_string())?;
+            Err(err)
+        }
+        Err(e) => Err(PyValueError::new_err(e.to_string())),
+    }
+}
+
 fn register_child_module(parent_module: &Bound<'_, PyModule>) -> PyResult<()> {
     let m = PyModule::new(parent_module.py(), "json_schema")?;
     parent_module.add_submodule(&m)?;
@@ -503,7 +932,12 @@ fn register_child_module(parent_module: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add("WHITESPACE", json_schema::WHITESPACE)?;
     m.add("EMAIL", json_schema::EMAIL)?;
     m.add("URI", json_schema::URI)?;
+    m.add("BYTE", json_schema::BYTE)?;
+    m.add("BINARY", json_schema::BINARY)?;
+    m.add("COLOR", json_schema::COLOR)?;
     m.add_function(wrap_pyfunction!(build_regex_from_schema_py, &m)?)?;
+    m.add_function(wrap_pyfunction!(build_regex_from_schema_verbose_py, &m)?)?;
+    m.add("JsonSchemaError", m.py().get_type::<JsonSchemaError>())?;
 
     let sys = PyModule::import(m.py(), "sys")?;
     let sys_modules_bind = sys.as_ref().getattr("modules")?;
@@ -526,6 +960,11 @@ fn outlines_core(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyIndex>()?;
     m.add_class::<PyVocabulary>()?;
     m.add_class::<PyGuide>()?;
+    m.add_class::<PyMultiGuide>()?;
+    m.add_class::<PyTransitionsIterator>()?;
+    m.add_class::<PyVocabularyIterator>()?;
+    m.add_function(wrap_pyfunction!(build_indexes_py, m)?)?;
+    m.add_function(wrap_pyfunction!(write_masks_into_py, m)?)?;
     register_child_module(m)?;
 
     Ok(())
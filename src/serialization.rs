@@ -0,0 +1,157 @@
+//! Versioned binary serialization of the crate's objects.
+//!
+//! Every blob starts with a header made of [`MAGIC`] bytes followed by the little-endian
+//! [`VERSION`] of the layout, so blobs produced by an incompatible version of the crate
+//! are rejected instead of being silently misread.
+//!
+//! Blobs of older versions, down to [`MIN_VERSION`], are still accepted, and types which
+//! layout changed read the blob's version from [`DecodeContext`]. Blobs without a header,
+//! produced before it was introduced, are treated as version 1.
+
+use std::io::{Cursor, Read, Write};
+
+use bincode::{config, Decode, Encode};
+
+use crate::{Error, Result};
+
+/// Marks the start of a versioned blob.
+pub const MAGIC: &[u8; 4] = b"OTLC";
+
+/// Version of the serialized layout, bumped on every incompatible change.
+///
+/// - `1`: the initial layout.
+/// - `2`: `Vocabulary` stores its EOS token and `Index` the fingerprint of its DFA.
+pub const VERSION: u16 = 2;
+
+/// The oldest version of the serialized layout, which is still accepted.
+pub const MIN_VERSION: u16 = 1;
+
+const HEADER_LEN: usize = MAGIC.len() + std::mem::size_of::<u16>();
+
+/// Context of decoding, which tells the version of the blob's layout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeContext {
+    /// Version of the serialized layout.
+    pub version: u16,
+}
+
+/// Encodes a value into a vector, prepended by the header.
+pub fn encode_to_vec<T: Encode>(value: &T) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    encode_into_writer(value, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// Decodes a value from a slice, validating the header if present.
+pub fn decode_from_slice<T: Decode<DecodeContext>>(bytes: &[u8]) -> Result<T> {
+    decode_from_reader(Cursor::new(bytes))
+}
+
+/// Encodes a value straight into a writer, prepended by the header.
+pub fn encode_into_writer<T: Encode, W: Write>(value: &T, mut writer: W) -> Result<()> {
+    writer
+        .write_all(MAGIC)
+        .and_then(|_| writer.write_all(&VERSION.to_le_bytes()))
+        .map_err(|e| bincode::error::EncodeError::Io { inner: e, index: 0 })?;
+    bincode::encode_into_std_write(value, &mut writer, config::standard())?;
+    Ok(())
+}
+
+/// Decodes a value from a reader, validating the header if present.
+pub fn decode_from_reader<T: Decode<DecodeContext>, R: Read>(mut reader: R) -> Result<T> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    (&mut reader)
+        .take(HEADER_LEN as u64)
+        .read_to_end(&mut header)
+        .map_err(|e| bincode::error::DecodeError::Io {
+            inner: e,
+            additional: HEADER_LEN,
+        })?;
+
+    if header.len() == HEADER_LEN && header.starts_with(MAGIC) {
+        let found = u16::from_le_bytes([header[MAGIC.len()], header[MAGIC.len() + 1]]);
+        if !(MIN_VERSION..=VERSION).contains(&found) {
+            return Err(Error::IncompatibleSerializedVersion {
+                found,
+                expected: VERSION,
+            });
+        }
+        Ok(bincode::decode_from_std_read_with_context(
+            &mut reader,
+            config::standard(),
+            DecodeContext { version: found },
+        )?)
+    } else {
+        // Headerless blob, consumed bytes belong to the value itself.
+        let mut reader = Cursor::new(header).chain(reader);
+        Ok(bincode::decode_from_std_read_with_context(
+            &mut reader,
+            config::standard(),
+            DecodeContext { version: 1 },
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    fn vocabulary() -> Vocabulary {
+        let mut vocabulary = Vocabulary::new(3);
+        for (token, id) in [("a", 0), ("b", 1), ("ab", 2)] {
+            vocabulary.try_insert(token, id).expect("Insert failed");
+        }
+        vocabulary
+    }
+
+    #[test]
+    fn round_trip_with_header() {
+        let vocabulary = vocabulary();
+        let bytes = encode_to_vec(&vocabulary).expect("Encoding failed");
+        assert!(bytes.starts_with(MAGIC));
+        assert_eq!(&bytes[MAGIC.len()..HEADER_LEN], &VERSION.to_le_bytes());
+
+        let decoded: Vocabulary = decode_from_slice(&bytes).expect("Decoding failed");
+        assert_eq!(decoded, vocabulary);
+    }
+
+    #[test]
+    fn headerless_blob_is_first_version() {
+        let vocabulary = vocabulary();
+        // Vocabulary's layout of version 1, without the EOS token.
+        let bytes = bincode::encode_to_vec(
+            (vocabulary.eos_token_id(), vocabulary.tokens()),
+            config::standard(),
+        )
+        .expect("Encoding failed");
+
+        let decoded: Vocabulary = decode_from_slice(&bytes).expect("Decoding failed");
+        assert_eq!(decoded, vocabulary);
+    }
+
+    #[test]
+    fn incompatible_version_rejected() {
+        let mut bytes = encode_to_vec(&vocabulary()).expect("Encoding failed");
+        bytes[MAGIC.len()..HEADER_LEN].copy_from_slice(&(VERSION + 1).to_le_bytes());
+
+        match decode_from_slice::<Vocabulary>(&bytes) {
+            Err(Error::IncompatibleSerializedVersion { found, expected }) => {
+                assert_eq!(found, VERSION + 1);
+                assert_eq!(expected, VERSION);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn corrupted_blob_rejected() {
+        let mut bytes = encode_to_vec(&vocabulary()).expect("Encoding failed");
+        bytes.truncate(HEADER_LEN + 2);
+
+        match decode_from_slice::<Vocabulary>(&bytes) {
+            Err(Error::DecodeError(_)) => {}
+            _ => unreachable!(),
+        }
+    }
+}
@@ -1,17 +1,21 @@
 //! Creates `Vocabulary` manually or from pretrained large language model.
 
+use bincode::de::Decoder;
+use bincode::error::DecodeError;
 use bincode::{Decode, Encode};
 #[cfg(feature = "hugginface-hub")]
 use locator::{HFLocator, Locator};
 #[cfg(feature = "hugginface-hub")]
 use processor::TokenProcessor;
-use rustc_hash::FxHashMap as HashMap;
+use rustc_hash::{FxHashMap as HashMap, FxHashSet as HashSet};
 #[cfg(feature = "hugginface-hub")]
 use tokenizers::normalizers::Sequence;
 #[cfg(feature = "hugginface-hub")]
 use tokenizers::{NormalizerWrapper, Tokenizer};
 
+use crate::fingerprint::fingerprint;
 use crate::prelude::*;
+use crate::serialization::DecodeContext;
 use crate::{Error, Result};
 
 #[cfg(feature = "hugginface-hub")]
@@ -19,6 +23,10 @@ mod locator;
 #[cfg(feature = "hugginface-hub")]
 mod processor;
 
+/// Custom decoding of a token into bytes.
+#[cfg(feature = "hugginface-hub")]
+type ProcessorFn<'a> = &'a dyn Fn(&str) -> Result<Vec<u8>>;
+
 /// `Vocabulary` of large language model.
 ///
 /// ## Examples
@@ -62,10 +70,12 @@ mod processor;
 /// ```
 "##
 )]
-#[derive(Clone, Debug, Default, PartialEq, Encode, Decode)]
+#[derive(Clone, Debug, Default, PartialEq, Encode)]
 pub struct Vocabulary {
     eos_token_id: TokenId,
     tokens: HashMap<Token, Vec<TokenId>>,
+    /// Bytes of the end of the sentence token, if known.
+    eos_token: Option<Token>,
 }
 
 impl Vocabulary {
@@ -74,7 +84,32 @@ impl Vocabulary {
         Self {
             eos_token_id,
             tokens: HashMap::default(),
+            eos_token: None,
+        }
+    }
+
+    /// Creates the vocabulary from pairs of tokens' bytes and their token ids.
+    pub fn from_iter_bytes(
+        eos_token_id: TokenId,
+        iter: impl IntoIterator<Item = (Vec<u8>, TokenId)>,
+    ) -> Result<Self> {
+        let mut vocabulary = Vocabulary::new(eos_token_id);
+        for (token, token_id) in iter {
+            vocabulary.try_insert(token, token_id)?;
         }
+        Ok(vocabulary)
+    }
+
+    /// Creates the vocabulary from pairs of string tokens and their token ids.
+    pub fn from_iter_str<'a>(
+        eos_token_id: TokenId,
+        iter: impl IntoIterator<Item = (&'a str, TokenId)>,
+    ) -> Result<Self> {
+        Self::from_iter_bytes(
+            eos_token_id,
+            iter.into_iter()
+                .map(|(token, token_id)| (token.as_bytes().to_vec(), token_id)),
+        )
     }
 
     /// Creates the vocabulary of pre-trained model from Hugging Face Hub.
@@ -83,7 +118,22 @@ impl Vocabulary {
         model: &str,
         parameters: Option<FromPretrainedParameters>,
     ) -> Result<Self> {
-        Self::from_pretrained_with_locator::<HFLocator>(model, parameters)
+        Self::from_pretrained_with_locator::<HFLocator>(model, parameters, None)
+    }
+
+    /// Creates the vocabulary of pre-trained model from Hugging Face Hub, decoding tokens
+    /// into bytes with a custom processor.
+    ///
+    /// Processor replaces the built-in one, which supports only byte level and byte fallback
+    /// tokenizers, so vocabularies of tokenizers with non-standard decoding could be built.
+    /// It's applied to every token except the EOS token, added tokens are inserted as is.
+    #[cfg(feature = "hugginface-hub")]
+    pub fn from_pretrained_with_processor(
+        model: &str,
+        parameters: Option<FromPretrainedParameters>,
+        processor: impl Fn(&str) -> Result<Vec<u8>>,
+    ) -> Result<Self> {
+        Self::from_pretrained_with_locator::<HFLocator>(model, parameters, Some(&processor))
     }
 
     #[doc(hidden)]
@@ -92,6 +142,7 @@ impl Vocabulary {
     fn from_pretrained_with_locator<L: Locator>(
         model: &str,
         parameters: Option<FromPretrainedParameters>,
+        processor: Option<ProcessorFn>,
     ) -> Result<Self> {
         let mut tokenizer = Tokenizer::from_pretrained(model, parameters.clone())?;
         Self::filter_prepend_normalizers(&mut tokenizer);
@@ -107,22 +158,34 @@ impl Vocabulary {
 
         // Start building the vocabulary from eos_token_id and added tokens.
         let mut vocabulary = Vocabulary::new(eos_token_id);
+        if let Some(eos_token) = tokenizer.id_to_token(eos_token_id) {
+            vocabulary.set_eos_token(eos_token);
+        }
         for (id, added_token) in tokenizer.get_added_tokens_decoder().iter() {
             if !added_token.special && id != &eos_token_id {
                 vocabulary.try_insert(added_token.content.clone(), *id)?
             }
         }
 
-        // Process each vocabulary token according to the tokenizer's level.
-        let Ok(processor) = TokenProcessor::new(&tokenizer) else {
-            return Err(Error::UnsupportedTokenizer {
-                model: model.to_string(),
-                reason: "Token processor".to_string(),
-            });
+        // Process each vocabulary token according to the tokenizer's level,
+        // unless a custom processor is provided.
+        let default_processor;
+        let processor = match processor {
+            Some(processor) => processor,
+            None => {
+                let Ok(token_processor) = TokenProcessor::new(&tokenizer) else {
+                    return Err(Error::UnsupportedTokenizer {
+                        model: model.to_string(),
+                        reason: "Token processor".to_string(),
+                    });
+                };
+                default_processor = move |token: &str| token_processor.process(token);
+                &default_processor
+            }
         };
         for (token, token_id) in tokenizer.get_vocab(false) {
             if token_id != eos_token_id {
-                let processed_token = processor.process(&token)?;
+                let processed_token = processor(&token)?;
                 vocabulary.try_insert(processed_token, token_id)?;
             }
         }
@@ -140,12 +203,49 @@ impl Vocabulary {
         self.tokens.get(token.as_ref())
     }
 
+    /// Returns ids of all tokens, deduplicated and in ascending order, along with EOS token id
+    /// if `include_eos` is set.
+    pub fn all_token_ids(&self, include_eos: bool) -> Vec<TokenId> {
+        let mut token_ids: Vec<TokenId> = self.tokens.values().flatten().copied().collect();
+        if include_eos {
+            token_ids.push(self.eos_token_id);
+        }
+        token_ids.sort_unstable();
+        token_ids.dedup();
+        token_ids
+    }
+
+    /// Returns the length in bytes of the longest token, or 0 if there are no tokens.
+    pub fn max_token_len(&self) -> usize {
+        self.tokens.keys().map(Vec::len).max().unwrap_or(0)
+    }
+
+    /// Checks if any token starts with the given bytes.
+    pub fn has_token_prefix(&self, bytes: &[u8]) -> bool {
+        self.tokens.keys().any(|token| token.starts_with(bytes))
+    }
+
     /// Gets the identifier of the special end of the sentence token.
     pub fn eos_token_id(&self) -> TokenId {
         self.eos_token_id
     }
 
+    /// Gets the bytes of the special end of the sentence token, if known.
+    pub fn eos_token(&self) -> Option<&Token> {
+        self.eos_token.as_ref()
+    }
+
+    /// Sets the bytes of the special end of the sentence token.
+    ///
+    /// The token is kept apart from the other tokens, it's informational only.
+    pub fn set_eos_token(&mut self, token: impl Into<Token>) {
+        self.eos_token = Some(token.into());
+    }
+
     /// Inserts a token to the vocabulary with the specified identifier.
+    ///
+    /// The identifier is appended to ones the token already has, see [`Vocabulary::set`]
+    /// to replace them instead.
     pub fn try_insert(&mut self, token: impl Into<Token>, id: TokenId) -> Result<(), Error> {
         if id == self.eos_token_id {
             return Err(Error::EOSTokenDisallowed);
@@ -155,12 +255,86 @@ impl Vocabulary {
         Ok(())
     }
 
+    /// Sets the only identifier of a token, replacing all identifiers it had, unlike
+    /// [`Vocabulary::try_insert`], which appends the identifier to them.
+    pub fn set(&mut self, token: impl Into<Token>, id: TokenId) -> Result<()> {
+        if id == self.eos_token_id {
+            return Err(Error::EOSTokenDisallowed);
+        }
+        self.tokens.insert(token.into(), vec![id]);
+        Ok(())
+    }
+
+    /// Inserts a token to the vocabulary, where a token of `<0x__>` form, used by byte fallback
+    /// tokenizers, is inserted as the single raw byte its hex digits stand for.
+    ///
+    /// Tokens of any other form are inserted as they are, while `<0x__>` tokens, which aren't
+    /// made of two hex digits, fail with [`Error::MalformedByteFallbackToken`].
+    pub fn try_insert_byte_fallback(&mut self, repr: &str, id: TokenId) -> Result<()> {
+        match repr
+            .strip_prefix("<0x")
+            .and_then(|hex| hex.strip_suffix('>'))
+        {
+            // Unlike `from_str_radix` alone, a sign, like in `<0x+F>`, isn't accepted.
+            Some(hex) if hex.len() == 2 => match u8::from_str_radix(hex, 16) {
+                Ok(byte) if hex.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                    self.try_insert(vec![byte], id)
+                }
+                _ => Err(Error::MalformedByteFallbackToken(repr.into())),
+            },
+            _ => self.try_insert(repr, id),
+        }
+    }
+
+    /// Inserts all tokens with their identifiers and returns the number of inserted entries.
+    ///
+    /// Entries are checked before insertion, so on error none of them is inserted and
+    /// the error points to the position of the first offending entry.
+    pub fn try_insert_many(
+        &mut self,
+        entries: impl IntoIterator<Item = (Token, TokenId)>,
+    ) -> Result<usize> {
+        let entries: Vec<(Token, TokenId)> = entries.into_iter().collect();
+        if let Some(index) = entries.iter().position(|(_, id)| *id == self.eos_token_id) {
+            return Err(Error::EOSTokenDisallowedAt { index });
+        }
+        let count = entries.len();
+        for (token, id) in entries {
+            self.tokens.entry(token).or_default().push(id);
+        }
+        Ok(count)
+    }
+
     /// Removes a given token from the vocabulary.
     pub fn remove(&mut self, token: impl Into<Token>) {
         let token = token.into();
         self.tokens.remove(&token);
     }
 
+    /// Returns a vocabulary containing only tokens with the given ids, e.g. to shrink it down
+    /// to [`Index::used_token_ids`](crate::index::Index::used_token_ids).
+    ///
+    /// Tokens left without any ids are dropped, EOS token is kept as it is.
+    pub fn project_to(&self, ids: &HashSet<TokenId>) -> Vocabulary {
+        let tokens = self
+            .tokens
+            .iter()
+            .filter_map(|(token, token_ids)| {
+                let token_ids: Vec<TokenId> = token_ids
+                    .iter()
+                    .filter(|id| ids.contains(id))
+                    .copied()
+                    .collect();
+                (!token_ids.is_empty()).then(|| (token.clone(), token_ids))
+            })
+            .collect();
+        Vocabulary {
+            eos_token_id: self.eos_token_id,
+            tokens,
+            eos_token: self.eos_token.clone(),
+        }
+    }
+
     pub fn len(&self) -> usize {
         // +1 for eos_token_id which is not in self.tokens map.
         self.tokens.values().map(|ids| ids.len()).sum::<usize>() + 1
@@ -170,6 +344,54 @@ impl Vocabulary {
         self.tokens.is_empty()
     }
 
+    /// Deduplicates and sorts token ids of every token.
+    ///
+    /// Keeps equality and serialization of logically identical vocabularies independent
+    /// of how their tokens were inserted.
+    pub fn normalize(&mut self) {
+        for ids in self.tokens.values_mut() {
+            ids.sort_unstable();
+            ids.dedup();
+        }
+    }
+
+    /// Serializes the vocabulary in a canonical form: entries sorted by token bytes and
+    /// token ids sorted within every entry.
+    ///
+    /// Unlike bincode encoding of `Vocabulary` itself, the output doesn't depend on insertion
+    /// order, so it's the stable form to use for hashing or caching.
+    pub fn to_sorted_bytes(&self) -> Vec<u8> {
+        let mut entries: Vec<(&Token, Vec<TokenId>)> = self
+            .tokens
+            .iter()
+            .map(|(token, ids)| {
+                let mut ids = ids.clone();
+                ids.sort_unstable();
+                (token, ids)
+            })
+            .collect();
+        entries.sort_unstable_by_key(|(token, _)| *token);
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.eos_token_id.to_le_bytes());
+        for (token, ids) in entries {
+            bytes.extend_from_slice(&(token.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(token);
+            bytes.extend_from_slice(&(ids.len() as u64).to_le_bytes());
+            for id in ids {
+                bytes.extend_from_slice(&id.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Returns a stable hash of the vocabulary's contents, computed over [`Vocabulary::to_sorted_bytes`].
+    ///
+    /// Logically equal vocabularies have the same fingerprint, regardless of insertion order.
+    pub fn fingerprint(&self) -> u64 {
+        fingerprint(&self.to_sorted_bytes())
+    }
+
     /// Filters out `Prepend` kind of tokenizer's normalizers.
     #[cfg(feature = "hugginface-hub")]
     fn filter_prepend_normalizers(tokenizer: &mut Tokenizer) {
@@ -224,6 +446,26 @@ impl std::fmt::Display for Vocabulary {
     }
 }
 
+impl Decode<DecodeContext> for Vocabulary {
+    fn decode<D: Decoder<Context = DecodeContext>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let eos_token_id = TokenId::decode(decoder)?;
+        let tokens = HashMap::<Token, Vec<TokenId>>::decode(decoder)?;
+        // Layout of version 1 ends right after the tokens.
+        let eos_token = if decoder.context().version >= 2 {
+            Option::<Token>::decode(decoder)?
+        } else {
+            None
+        };
+        Ok(Vocabulary {
+            eos_token_id,
+            tokens,
+            eos_token,
+        })
+    }
+}
+
+bincode::impl_borrow_decode_with_context!(Vocabulary, DecodeContext);
+
 impl TryFrom<(TokenId, HashMap<Token, Vec<TokenId>>)> for Vocabulary {
     type Error = Error;
 
@@ -235,6 +477,7 @@ impl TryFrom<(TokenId, HashMap<Token, Vec<TokenId>>)> for Vocabulary {
         Ok(Vocabulary {
             eos_token_id,
             tokens,
+            eos_token: None,
         })
     }
 }
@@ -256,13 +499,39 @@ impl TryFrom<(TokenId, HashMap<String, Vec<TokenId>>)> for Vocabulary {
                     }
                 })
                 .collect::<Result<HashMap<Token, Vec<TokenId>>, _>>()?,
+            eos_token: None,
         })
     }
 }
 
+impl TryFrom<(TokenId, HashMap<Token, TokenId>)> for Vocabulary {
+    type Error = Error;
+
+    fn try_from(values: (TokenId, HashMap<Token, TokenId>)) -> Result<Self, Self::Error> {
+        let (eos_token_id, tokens) = values;
+        let tokens: HashMap<Token, Vec<TokenId>> =
+            tokens.into_iter().map(|(k, id)| (k, vec![id])).collect();
+        Vocabulary::try_from((eos_token_id, tokens))
+    }
+}
+
+impl TryFrom<(TokenId, HashMap<String, TokenId>)> for Vocabulary {
+    type Error = Error;
+
+    fn try_from(values: (TokenId, HashMap<String, TokenId>)) -> Result<Self, Self::Error> {
+        let (eos_token_id, tokens) = values;
+        let tokens: HashMap<Token, Vec<TokenId>> = tokens
+            .into_iter()
+            .map(|(k, id)| (k.into_bytes(), vec![id]))
+            .collect();
+        Vocabulary::try_from((eos_token_id, tokens))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::serialization;
 
     #[test]
     fn basic_interface() {
@@ -309,6 +578,229 @@ mod tests {
         assert_eq!(vocabulary.token_ids("six"), None);
     }
 
+    #[test]
+    fn from_iter_constructors() {
+        let mut expected = Vocabulary::new(3);
+        for (token, token_id) in [("a", 0), ("b", 1), ("a", 2)] {
+            expected.try_insert(token, token_id).expect("Insert failed");
+        }
+
+        let vocabulary = Vocabulary::from_iter_str(3, [("a", 0), ("b", 1), ("a", 2)])
+            .expect("Vocabulary failed");
+        assert_eq!(vocabulary, expected);
+
+        let vocabulary = Vocabulary::from_iter_bytes(
+            3,
+            [(b"a".to_vec(), 0), (b"b".to_vec(), 1), (b"a".to_vec(), 2)],
+        )
+        .expect("Vocabulary failed");
+        assert_eq!(vocabulary, expected);
+
+        let result = Vocabulary::from_iter_str(3, [("a", 0), ("eos", 3)]);
+        assert!(matches!(result, Err(Error::EOSTokenDisallowed)));
+    }
+
+    #[test]
+    fn max_token_len_and_prefixes() {
+        let mut vocabulary = Vocabulary::new(4);
+        assert_eq!(vocabulary.max_token_len(), 0);
+        assert!(!vocabulary.has_token_prefix(b"a"));
+
+        for (token, token_id) in [("a", 0), ("abc", 1), ("bc", 2)] {
+            vocabulary
+                .try_insert(token, token_id)
+                .expect("Insert failed");
+        }
+        vocabulary
+            .try_insert(vec![0xE2, 0x82, 0xAC, 0x21], 3)
+            .expect("Insert failed");
+        assert_eq!(vocabulary.max_token_len(), 4);
+
+        for prefix in [&b""[..], b"a", b"ab", b"abc", b"b", &[0xE2, 0x82]] {
+            assert!(vocabulary.has_token_prefix(prefix), "{:?}", prefix);
+        }
+        for prefix in [&b"abcd"[..], b"c", b"ba", &[0x82]] {
+            assert!(!vocabulary.has_token_prefix(prefix), "{:?}", prefix);
+        }
+    }
+
+    #[test]
+    fn try_insert_many_tokens() {
+        let mut vocabulary = Vocabulary::new(3);
+        let entries = [(b"a".to_vec(), 0), (b"b".to_vec(), 1), (b"a".to_vec(), 2)];
+        let count = vocabulary
+            .try_insert_many(entries.clone())
+            .expect("Insert failed");
+        assert_eq!(count, 3);
+        assert_eq!(
+            vocabulary,
+            Vocabulary::from_iter_bytes(3, entries).expect("Vocabulary failed")
+        );
+
+        let mut vocabulary = Vocabulary::new(3);
+        let entries = [(b"a".to_vec(), 0), (b"eos".to_vec(), 3), (b"b".to_vec(), 1)];
+        let result = vocabulary.try_insert_many(entries);
+        assert!(matches!(
+            result,
+            Err(Error::EOSTokenDisallowedAt { index: 1 })
+        ));
+        assert!(vocabulary.is_empty());
+    }
+
+    #[test]
+    fn normalize_dedups_and_sorts_ids() {
+        let mut vocabulary = Vocabulary::new(0);
+        for (token, id) in [("a", 3), ("a", 1), ("a", 3), ("b", 2), ("a", 1)] {
+            vocabulary.try_insert(token, id).expect("Insert failed");
+        }
+        assert_eq!(vocabulary.token_ids("a"), Some(&vec![3, 1, 3, 1]));
+
+        vocabulary.normalize();
+        assert_eq!(vocabulary.token_ids("a"), Some(&vec![1, 3]));
+        assert_eq!(vocabulary.token_ids("b"), Some(&vec![2]));
+
+        let mut other = Vocabulary::new(0);
+        for (token, id) in [("b", 2), ("a", 1), ("a", 3)] {
+            other.try_insert(token, id).expect("Insert failed");
+        }
+        assert_eq!(vocabulary, other);
+    }
+
+    #[test]
+    fn sorted_bytes_ignore_insertion_order() {
+        let mut vocabulary = Vocabulary::new(0);
+        for (token, id) in [("a", 1), ("b", 2), ("a", 3), ("ccc", 4)] {
+            vocabulary.try_insert(token, id).expect("Insert failed");
+        }
+
+        let tokens: HashMap<String, Vec<TokenId>> = HashMap::from_iter([
+            ("ccc".to_string(), vec![4]),
+            ("a".to_string(), vec![3, 1]),
+            ("b".to_string(), vec![2]),
+        ]);
+        let other = Vocabulary::try_from((0, tokens)).expect("Vocabulary failed");
+
+        assert_eq!(vocabulary.to_sorted_bytes(), other.to_sorted_bytes());
+
+        vocabulary.remove("ccc");
+        assert_ne!(vocabulary.to_sorted_bytes(), other.to_sorted_bytes());
+    }
+
+    #[test]
+    fn fingerprint_is_stable() {
+        let mut vocabulary = Vocabulary::new(0);
+        let mut other = Vocabulary::new(0);
+        for (token, id) in [("a", 1), ("b", 2), ("a", 3)] {
+            vocabulary.try_insert(token, id).expect("Insert failed");
+        }
+        for (token, id) in [("a", 3), ("a", 1), ("b", 2)] {
+            other.try_insert(token, id).expect("Insert failed");
+        }
+        assert_eq!(vocabulary.fingerprint(), other.fingerprint());
+
+        other.try_insert("c", 4).expect("Insert failed");
+        assert_ne!(vocabulary.fingerprint(), other.fingerprint());
+    }
+
+    #[test]
+    fn all_token_ids_sorted() {
+        let mut vocabulary = Vocabulary::new(2);
+        for (token, token_id) in [("c", 5), ("a", 0), ("b", 3), ("b", 1), ("d", 3)] {
+            vocabulary
+                .try_insert(token, token_id)
+                .expect("Insert failed");
+        }
+        assert_eq!(vocabulary.all_token_ids(false), vec![0, 1, 3, 5]);
+        assert_eq!(vocabulary.all_token_ids(true), vec![0, 1, 2, 3, 5]);
+        assert_eq!(Vocabulary::new(2).all_token_ids(true), vec![2]);
+    }
+
+    #[test]
+    fn insert_byte_fallback_tokens() {
+        let mut vocabulary = Vocabulary::new(3);
+        vocabulary
+            .try_insert_byte_fallback("<0x20>", 0)
+            .expect("Insert failed");
+        vocabulary
+            .try_insert_byte_fallback("<0xFF>", 1)
+            .expect("Insert failed");
+        vocabulary
+            .try_insert_byte_fallback("<0x2>", 2)
+            .expect("Insert failed");
+        assert_eq!(vocabulary.token_ids([0x20]), Some(&vec![0]));
+        assert_eq!(vocabulary.token_ids([0xFF]), Some(&vec![1]));
+        assert_eq!(vocabulary.token_ids("<0x2>"), Some(&vec![2]));
+        assert_eq!(vocabulary.token_ids("<0x20>"), None);
+
+        for repr in ["<0xZZ>", "<0x+F>"] {
+            match vocabulary.try_insert_byte_fallback(repr, 4) {
+                Err(Error::MalformedByteFallbackToken(token)) => assert_eq!(&*token, repr),
+                result => panic!("Expected malformed token error for {repr}, got {result:?}"),
+            }
+        }
+        assert!(matches!(
+            vocabulary.try_insert_byte_fallback("<0x00>", 3),
+            Err(Error::EOSTokenDisallowed)
+        ));
+    }
+
+    #[test]
+    fn eos_token_bytes() {
+        let mut vocabulary = Vocabulary::new(1);
+        assert_eq!(vocabulary.eos_token(), None);
+
+        vocabulary.set_eos_token("</s>");
+        assert_eq!(vocabulary.eos_token(), Some(&b"</s>".to_vec()));
+        assert!(vocabulary.token_ids("</s>").is_none());
+
+        let bytes = serialization::encode_to_vec(&vocabulary).expect("Encode failed");
+        let decoded: Vocabulary = serialization::decode_from_slice(&bytes).expect("Decode failed");
+        assert_eq!(decoded, vocabulary);
+    }
+
+    #[test]
+    fn decode_without_eos_token() {
+        let mut tokens: HashMap<Token, Vec<TokenId>> = HashMap::default();
+        tokens.insert(b"a".to_vec(), vec![0]);
+        // Layout of version 1, before `eos_token` was introduced.
+        let mut bytes = serialization::MAGIC.to_vec();
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend(
+            bincode::encode_to_vec((1 as TokenId, tokens.clone()), bincode::config::standard())
+                .expect("Encode failed"),
+        );
+        let decoded: Vocabulary = serialization::decode_from_slice(&bytes).expect("Decode failed");
+        assert_eq!(decoded.eos_token_id(), 1);
+        assert_eq!(decoded.tokens(), &tokens);
+        assert_eq!(decoded.eos_token(), None);
+    }
+
+    #[cfg(feature = "hugginface-hub")]
+    #[test]
+    fn pretrained_eos_token() {
+        let vocabulary =
+            Vocabulary::from_pretrained("openai-community/gpt2", None).expect("Vocabulary failed");
+        assert_eq!(vocabulary.eos_token(), Some(&b"<|endoftext|>".to_vec()));
+    }
+
+    #[cfg(feature = "hugginface-hub")]
+    #[test]
+    fn pretrained_with_custom_processor() {
+        let model = "openai-community/gpt2";
+        let tokenizer = Tokenizer::from_pretrained(model, None).expect("Tokenizer failed");
+        let vocabulary = Vocabulary::from_pretrained_with_processor(model, None, |token| {
+            Ok(token.to_uppercase().into_bytes())
+        })
+        .expect("Vocabulary failed");
+
+        let token_id = tokenizer.token_to_id("Ġal").expect("Token not found");
+        assert!(vocabulary
+            .token_ids("ĠAL")
+            .is_some_and(|ids| ids.contains(&token_id)));
+        assert!(vocabulary.token_ids(" al").is_none());
+        assert_eq!(vocabulary.eos_token_id(), 50256);
+    }
+
     #[test]
     fn new_empty_vocabulary_from_hashmap() {
         let map: HashMap<Token, Vec<TokenId>> = HashMap::default();
@@ -317,6 +809,49 @@ mod tests {
         assert!(vocabulary.tokens.is_empty());
     }
 
+    #[test]
+    fn insert_appends_while_set_replaces() {
+        let mut vocabulary = Vocabulary::new(3);
+        vocabulary.try_insert("a", 0).expect("Insert failed");
+        vocabulary.try_insert("a", 1).expect("Insert failed");
+        assert_eq!(vocabulary.token_ids("a"), Some(&vec![0, 1]));
+
+        vocabulary.set("a", 2).expect("Set failed");
+        assert_eq!(vocabulary.token_ids("a"), Some(&vec![2]));
+        vocabulary.set(b"b", 4).expect("Set failed");
+        assert_eq!(vocabulary.token_ids("b"), Some(&vec![4]));
+        assert_eq!(vocabulary.len(), 3);
+
+        assert!(matches!(
+            vocabulary.set("a", 3),
+            Err(Error::EOSTokenDisallowed)
+        ));
+        assert_eq!(vocabulary.token_ids("a"), Some(&vec![2]));
+    }
+
+    #[test]
+    fn new_vocabulary_from_single_id_hashmaps() {
+        let expected: HashMap<Token, Vec<TokenId>> =
+            HashMap::from_iter([(b"a".to_vec(), vec![0]), (b"bc".to_vec(), vec![1])]);
+        let expected = Vocabulary::try_from((2_u32, expected)).expect("Vocabulary failed");
+
+        let bytes: HashMap<Token, TokenId> =
+            HashMap::from_iter([(b"a".to_vec(), 0), (b"bc".to_vec(), 1)]);
+        let vocabulary = Vocabulary::try_from((2_u32, bytes)).expect("Vocabulary failed");
+        assert_eq!(vocabulary, expected);
+
+        let strings: HashMap<String, TokenId> =
+            HashMap::from_iter([("a".to_string(), 0), ("bc".to_string(), 1)]);
+        let vocabulary = Vocabulary::try_from((2_u32, strings)).expect("Vocabulary failed");
+        assert_eq!(vocabulary, expected);
+
+        let with_eos: HashMap<String, TokenId> = HashMap::from_iter([("a".to_string(), 2)]);
+        assert!(matches!(
+            Vocabulary::try_from((2_u32, with_eos)),
+            Err(Error::EOSTokenDisallowed)
+        ));
+    }
+
     #[cfg(feature = "hugginface-hub")]
     #[test]
     fn supported_pretrained_models() {
@@ -466,7 +1001,7 @@ mod tests {
     #[test]
     fn unable_to_locate_eos_token_id_error() {
         let model = "hf-internal-testing/tiny-random-XLMRobertaXLForCausalLM";
-        let vocabulary = Vocabulary::from_pretrained_with_locator::<NoneLocator>(model, None);
+        let vocabulary = Vocabulary::from_pretrained_with_locator::<NoneLocator>(model, None, None);
 
         match vocabulary {
             Err(Error::UnsupportedTokenizer { model, reason }) => {
@@ -4,7 +4,7 @@ from typing import Dict, List, Union
 
 import pytest
 
-from outlines_core import Guide, Index, Vocabulary
+from outlines_core import Guide, Index, Vocabulary, write_masks_into
 
 
 @pytest.fixture(scope="session")
@@ -103,6 +103,31 @@ def test_str_and_bytes_produce_the_same():
     assert guide2.is_finished()
 
 
+def test_save_and_load(index, tmp_path):
+    guide = Guide(index)
+    guide.advance(1)
+    path = str(tmp_path / "guide.bin")
+    guide.save(path)
+    loaded = Guide.load(path)
+    assert loaded.get_state() == guide.get_state()
+    assert sorted(loaded.get_tokens()) == sorted(guide.get_tokens())
+
+
+def test_serialization_with_shared_index(index):
+    guides = [Guide(index, max_rollback=3) for _ in range(3)]
+    guides[1].advance(1)
+
+    data = [guide.to_binary_without_index() for guide in guides]
+    assert all(len(d) < len(pickle.dumps(guide)) for d, guide in zip(data, guides))
+
+    restored = [Guide.from_binary_with_index(d, index) for d in data]
+    assert restored == guides
+    assert restored[1].get_state() == guides[1].get_state()
+    assert restored[1].get_max_rollback() == 3
+    restored[1].rollback_state(1)
+    assert restored[1].get_state() == index.get_initial_state()
+
+
 def test_pickling(index):
     guide = Guide(index)
     serialized = pickle.dumps(guide)
@@ -167,6 +192,32 @@ def test_write_mask_into(index):
     ), f"mask and expected mask do not match. Mask: {mask[0]}, Expected: {expected_mask}"
 
 
+def test_write_masks_into(index):
+    import torch
+
+    guide1 = Guide(index)
+    guide2 = Guide(index)
+    guide2.advance(1)
+    guides = [guide1, guide2]
+
+    masks = torch.full((2, 1), -1, dtype=torch.uint32)
+    write_masks_into(
+        guides, masks.data_ptr(), masks.shape[0], masks.shape[1], masks.element_size()
+    )
+
+    for row, guide in enumerate(guides):
+        mask = torch.tensor([-1], dtype=torch.uint32)
+        guide.write_mask_into(mask.data_ptr(), mask.numel(), mask.element_size())
+        assert torch.equal(masks[row], mask)
+
+    with pytest.raises(ValueError, match="Invalid number of rows"):
+        write_masks_into(guides, masks.data_ptr(), 1, 1, masks.element_size())
+    with pytest.raises(ValueError, match="Invalid row size"):
+        write_masks_into(guides, masks.data_ptr(), 2, 0, masks.element_size())
+    with pytest.raises(ValueError, match="Invalid element size"):
+        write_masks_into(guides, masks.data_ptr(), 2, 1, 8)
+
+
 def test_write_mask_into_interface(index):
     import torch
 
@@ -183,6 +234,29 @@ def test_write_mask_into_interface(index):
         guide.write_mask_into(5, mask.numel(), mask.element_size())
 
 
+def test_write_mask_into_out_of_range_token():
+    import torch
+
+    # Token id 40 of a sparse vocabulary doesn't fit into a single 32-bit element.
+    vocabulary = Vocabulary(3, {"1": [1], "2": [40]})
+    guide = Guide(Index(r"[1-9]", vocabulary))
+    mask = torch.full((1, 1), -1, dtype=torch.uint32)
+
+    with pytest.raises(ValueError, match="doesn't fit into 1 elements"):
+        guide.write_mask_into(mask.data_ptr(), mask.numel(), mask.element_size())
+    with pytest.raises(ValueError, match="doesn't fit into 1 elements"):
+        write_masks_into(
+            [guide], mask.data_ptr(), 1, mask.shape[1], mask.element_size()
+        )
+    # The mask is left untouched.
+    assert torch.equal(mask, torch.full((1, 1), -1, dtype=torch.uint32))
+
+    # Buffer sized by the model's vocabulary fits it.
+    mask = torch.zeros((1, 2), dtype=torch.uint32)
+    guide.write_mask_into(mask.data_ptr(), mask.numel(), mask.element_size())
+    assert mask[0, 1] == 1 << (40 % 32)
+
+
 def test_rollback(index):
     guide = Guide(index, max_rollback=3)
 
@@ -196,6 +270,23 @@ def test_rollback(index):
     assert guide.get_state() == first_state
 
 
+def test_max_rollback(index):
+    guide = Guide(index, max_rollback=3)
+    assert guide.get_max_rollback() == 3
+    assert guide.get_allowed_rollback() == 0
+
+    guide.advance(1)
+    assert guide.get_allowed_rollback() == 1
+    # Final state allows only EOS token, which keeps the guide in the same state.
+    for _ in range(4):
+        guide.advance(3)
+    assert guide.get_allowed_rollback() == guide.get_max_rollback() == 3
+
+    guide.rollback_state(2)
+    assert guide.get_allowed_rollback() == 1
+    assert guide.get_max_rollback() == 3
+
+
 def test_rollback_interface(index):
     guide = Guide(index, max_rollback=3)
 
@@ -4,7 +4,7 @@ import pickle
 
 import pytest
 
-from outlines_core import Index, Vocabulary
+from outlines_core import Index, Vocabulary, build_indexes
 
 
 @pytest.fixture(scope="session")
@@ -42,6 +42,42 @@ def test_basic_interface(index):
     assert index.get_transitions() == expected_transitions
 
 
+def test_can_and_must_terminate():
+    vocabulary = Vocabulary(4, {"blah": [0], "1a": [1], "2": [2], "0": [3]})
+    index = Index("0|[1-9][0-9]*", vocabulary)
+    initial_state = index.get_initial_state()
+    assert not index.can_terminate(initial_state)
+
+    continuable = index.get_next_state(initial_state, 2)
+    assert index.can_terminate(continuable)
+    assert not index.must_terminate(continuable)
+
+    terminal = index.get_next_state(initial_state, 3)
+    assert index.can_terminate(terminal)
+    assert index.must_terminate(terminal)
+
+
+def test_case_insensitive_index():
+    vocabulary = Vocabulary(4, {"A": [0], "bC": [1], "abc": [2]})
+    index = Index("abc", vocabulary)
+    assert index.get_next_state(index.get_initial_state(), 0) is None
+
+    index = Index("abc", vocabulary, case_insensitive=True)
+    state = index.get_next_state(index.get_initial_state(), 0)
+    state = index.get_next_state(state, 1)
+    assert index.is_final_state(state)
+
+
+def test_terminating_tokens():
+    vocabulary = Vocabulary(4, {"blah": [0], "1a": [1], "2": [2], "0": [3]})
+    index = Index("0|[1-9][0-9]*", vocabulary)
+    initial_state = index.get_initial_state()
+    assert index.get_terminating_tokens(initial_state) == [2, 3]
+
+    terminal = index.get_next_state(initial_state, 3)
+    assert index.get_terminating_tokens(terminal) == []
+
+
 def test_pickling(index):
     serialized = pickle.dumps(index)
     deserialized = pickle.loads(serialized)
@@ -62,3 +98,53 @@ def test_deepcopy(index):
     assert is_deleted
 
     assert copy_index2 == index
+
+
+def test_save_and_load(index, tmp_path):
+    path = str(tmp_path / "index.bin")
+    index.save(path)
+    assert Index.load(path) == index
+
+
+def test_versioned_binary(index):
+    _, (binary_data,) = index.__reduce__()
+    assert binary_data[:4] == b"OTLC"
+    assert Index.from_binary(binary_data) == index
+
+    incompatible = binary_data[:4] + b"\xff\xff" + binary_data[6:]
+    with pytest.raises(ValueError, match="Incompatible serialized version"):
+        Index.from_binary(incompatible)
+
+
+def test_iter_transitions(index):
+    transitions = {}
+    for state, token_id, next_state in index.iter_transitions():
+        transitions.setdefault(state, {})[token_id] = next_state
+    assert transitions == index.get_transitions()
+
+
+def test_get_transitions_for_state(index):
+    transitions = index.get_transitions()
+    for state, row in transitions.items():
+        assert index.get_transitions_for_state(state) == row
+    assert index.get_transitions_for_state(999) is None
+
+
+def test_num_states_and_transitions(index):
+    transitions = index.get_transitions()
+    states = {index.get_initial_state()} | index.get_final_states()
+    for state, row in transitions.items():
+        states.add(state)
+        states.update(row.values())
+    assert index.num_states() == len(states)
+    assert index.num_transitions() == sum(len(row) for row in transitions.values())
+
+
+def test_build_indexes():
+    regex = r"[1-9]"
+    vocabularies = [
+        Vocabulary(3, {"1": [1], "2": [2]}),
+        Vocabulary(4, {"1": [1], "a": [2], "9": [3]}),
+    ]
+    indexes = build_indexes(regex, vocabularies)
+    assert indexes == [Index(regex, vocabulary) for vocabulary in vocabularies]
@@ -4,7 +4,11 @@ import re
 import pytest
 from pydantic import BaseModel
 
-from outlines_core.json_schema import build_regex_from_schema
+from outlines_core.json_schema import (
+    JsonSchemaError,
+    build_regex_from_schema,
+    build_regex_from_schema_verbose,
+)
 
 
 def test_build_regex_from_json_schema():
@@ -32,9 +36,33 @@ def test_invalid_json():
         build_regex_from_schema("{'name':")
 
 
+def test_verbose_error_pointer():
+    schema = json.dumps(
+        {
+            "type": "object",
+            "properties": {
+                "outer": {
+                    "type": "object",
+                    "properties": {"inner": {"type": "string", "format": "unknown"}},
+                }
+            },
+        }
+    )
+    with pytest.raises(JsonSchemaError) as exc_info:
+        build_regex_from_schema_verbose(schema)
+    assert exc_info.value.pointer == "/properties/outer/properties/inner"
+    assert "unknown" in exc_info.value.reason
+
+    schema = json.dumps({"type": "integer"})
+    assert build_regex_from_schema_verbose(schema) == build_regex_from_schema(schema)
+
+
 def test_types_presence_and_not_emptyness():
     from outlines_core.json_schema import (
+        BINARY,
         BOOLEAN,
+        BYTE,
+        COLOR,
         DATE,
         DATE_TIME,
         EMAIL,
@@ -49,7 +77,10 @@ def test_types_presence_and_not_emptyness():
         WHITESPACE,
     )
 
+    assert BINARY
     assert BOOLEAN
+    assert BYTE
+    assert COLOR
     assert DATE
     assert DATE_TIME
     assert EMAIL
@@ -0,0 +1,41 @@
+import pytest
+
+from outlines_core import Index, MultiGuide, Vocabulary
+
+
+@pytest.fixture(scope="session")
+def vocabulary() -> Vocabulary:
+    return Vocabulary(5, {"a": [0], "b": [1], "ab": [2], "1": [3], "ba": [4]})
+
+
+def test_intersection_of_allowed_tokens(vocabulary):
+    letters = Index("[ab]+", vocabulary)
+    short = Index(".{1,3}", vocabulary)
+    guide = MultiGuide([letters, short])
+
+    assert guide.get_states() == [
+        letters.get_initial_state(),
+        short.get_initial_state(),
+    ]
+    assert guide.get_tokens() == [0, 1, 2, 4]
+    assert not guide.is_finished()
+
+    assert guide.advance(2) == [0, 1, 5]
+    assert guide.is_finished()
+
+    assert guide.advance(1) == [5]
+    assert guide.is_finished()
+
+
+def test_disallowed_token(vocabulary):
+    guide = MultiGuide([Index("[ab]+", vocabulary), Index("a.*", vocabulary)])
+    states = guide.get_states()
+
+    with pytest.raises(ValueError):
+        guide.advance(1)
+    assert guide.get_states() == states
+
+
+def test_indexes_are_required():
+    with pytest.raises(ValueError):
+        MultiGuide([])
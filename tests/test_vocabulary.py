@@ -53,6 +53,25 @@ def test_string_and_bytes_as_tokens():
     assert len(vocabulary) == len(bvocabulary) == 3
 
 
+def test_explicit_dict_constructors():
+    eos_token_id = 3
+    tokens = {"1": [1], "a": [2]}
+    btokens = {b"1": [1], b"a": [2]}
+
+    vocabulary = Vocabulary.from_dict_str(eos_token_id, tokens)
+    bvocabulary = Vocabulary.from_dict_bytes(eos_token_id, btokens)
+    assert vocabulary == Vocabulary(eos_token_id, tokens)
+    assert bvocabulary == Vocabulary(eos_token_id, btokens)
+    assert vocabulary == bvocabulary
+
+    with pytest.raises(TypeError):
+        Vocabulary.from_dict_str(eos_token_id, btokens)
+    with pytest.raises(TypeError):
+        Vocabulary.from_dict_bytes(eos_token_id, tokens)
+    with pytest.raises(ValueError, match="EOS token should not be inserted"):
+        Vocabulary.from_dict_str(eos_token_id, {"1": [3]})
+
+
 def test_do_not_supports_other_types():
     eos_token_id = 0
 
@@ -69,6 +88,25 @@ def test_do_not_supports_other_types():
         Vocabulary(eos_token_id, {1: [1], 2: [2]})
 
 
+def test_items_and_keys():
+    tokens = {b"1": [1], b"a": [2, 4], b"\xff": [5]}
+    vocabulary = Vocabulary(3, tokens)
+
+    assert dict(vocabulary.items()) == tokens
+    assert set(vocabulary.keys()) == set(tokens)
+    assert len(vocabulary.keys()) == len(tokens)
+    assert Vocabulary(3, dict(vocabulary.items())) == vocabulary
+
+    # Iterators keep a snapshot of the vocabulary, even if it's modified meanwhile.
+    keys = vocabulary.keys()
+    next(keys)
+    vocabulary.insert(b"b", 6)
+    vocabulary.remove(b"1")
+    assert len(keys) == len(tokens) - 1
+    assert len(list(keys)) == len(tokens) - 1
+    assert set(vocabulary.keys()) == {b"a", b"\xff", b"b"}
+
+
 def test_get_bad_type(vocabulary):
     with pytest.raises(
         TypeError,
@@ -103,6 +141,12 @@ def test_pickling(vocabulary):
     assert deserialized == vocabulary
 
 
+def test_save_and_load(vocabulary, tmp_path):
+    path = str(tmp_path / "vocabulary.bin")
+    vocabulary.save(path)
+    assert Vocabulary.load(path) == vocabulary
+
+
 def test_deepcopy(vocabulary):
     vocabulary2 = copy.deepcopy(vocabulary)
     assert vocabulary2 == vocabulary